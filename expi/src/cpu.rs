@@ -1,24 +1,98 @@
 //! CPU specific operations.
 
 use core::arch::asm;
+use core::fmt;
+
+use mutex::WaitHint;
+use range::Range;
+
+use crate::fdt;
 
 pub mod exceptions;
+pub mod info;
 pub mod mmu;
 pub mod mp;
 pub mod pmu;
 pub mod time;
-
-/// Number of CPU cores.
-const NCORES: usize = 4;
+pub mod topology;
 
 /// CPU related error.
 #[derive(Debug)]
 pub enum Error {
     /// Invalid CPU core.
     InvalidCore(usize),
+
+    /// The core is part of a uniprocessor system, so it does not share the
+    /// inner shareable domain with other cores.
+    Uniprocessor,
+
+    /// SMP coherency could not be enabled.
+    SmpCoherencyDisabled,
+
+    /// The CPU topology has not been initialized.
+    Uninitialized,
+
+    /// The devicetree does not describe any CPU.
+    NoCpus,
+
+    /// A CPU node does not have a valid `reg` property.
+    MissingCpuReg,
+
+    /// The CPU with the provided affinity uses an unsupported enable method.
+    UnsupportedEnableMethod(u64),
+
+    /// The boot stacks region cannot hold the temporary stack of the CPU with
+    /// the provided affinity.
+    NoBootStack(u64),
+
+    /// The range cannot be removed from the identity mapping. See
+    /// [`mmu::unmap_identity`].
+    InvalidUnmapRange(Range),
+
+    /// FDT error.
+    FdtError(fdt::Error),
+}
+
+impl From<fdt::Error> for Error {
+    fn from(err: fdt::Error) -> Error {
+        Error::FdtError(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidCore(core) => write!(f, "invalid core: {core}"),
+            Error::Uniprocessor => write!(f, "uniprocessor system"),
+            Error::SmpCoherencyDisabled => {
+                write!(f, "SMP coherency could not be enabled")
+            }
+            Error::Uninitialized => {
+                write!(f, "CPU topology is not initialized")
+            }
+            Error::NoCpus => write!(f, "no CPUs found in the devicetree"),
+            Error::MissingCpuReg => write!(f, "missing CPU reg property"),
+            Error::UnsupportedEnableMethod(affinity) => {
+                write!(f, "unsupported enable method: CPU {affinity:#x}")
+            }
+            Error::NoBootStack(affinity) => {
+                write!(f, "no boot stack: CPU {affinity:#x}")
+            }
+            Error::InvalidUnmapRange(range) => write!(
+                f,
+                "cannot unmap [{:#x}, {:#x}]",
+                range.start(),
+                range.end()
+            ),
+            Error::FdtError(err) => write!(f, "FDT error: {err}"),
+        }
+    }
 }
 
 /// Represents a CPU core.
+///
+/// Cores are numbered following the order of the CPUs in the
+/// [`Topology`](topology::Topology).
 #[derive(Debug, Copy, Clone)]
 pub struct Core(usize);
 
@@ -26,7 +100,7 @@ impl TryFrom<usize> for Core {
     type Error = Error;
 
     fn try_from(core: usize) -> Result<Core, Error> {
-        if core >= NCORES {
+        if core >= topology::ncores()? {
             return Err(Error::InvalidCore(core));
         }
         Ok(Core(core))
@@ -64,3 +138,31 @@ pub fn wfe() {
 pub fn wfi() {
     unsafe { asm!("wfi") };
 }
+
+/// sev instruction.
+///
+/// Send Event is a hint instruction that causes an event to be signaled to
+/// all the PEs in the multiprocessor system. The data written before are made
+/// visible to the other PEs before sending the event.
+#[inline(always)]
+pub fn sev() {
+    unsafe { asm!("dsb ish", "sev") };
+}
+
+/// Waiting strategy for the blocking primitives of the [`mutex`] crate.
+///
+/// Waiters sleep in [`wfe`] and are woken by [`sev`]. A core waiting in
+/// [`wfe`] is also woken when an unmasked interrupt is taken, so an interrupt
+/// handler can signal a waiting core.
+#[derive(Debug)]
+pub struct Wfe;
+
+impl WaitHint for Wfe {
+    fn wait() {
+        wfe()
+    }
+
+    fn wake() {
+        sev()
+    }
+}
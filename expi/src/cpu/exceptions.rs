@@ -2,6 +2,8 @@
 
 use core::arch::asm;
 
+use mutex::IrqControl;
+
 /// Interrupt types.
 #[derive(Debug, Copy, Clone)]
 pub enum Interrupt {
@@ -119,6 +121,32 @@ impl Interrupt {
     }
 }
 
+/// Saves and restores the IRQ and FIQ masks of the DAIF register.
+///
+/// It is used by the interrupt-safe mutexes protecting the global resources,
+/// so interrupt handlers can use them without deadlocking the interrupted
+/// code.
+pub struct Daif;
+
+unsafe impl IrqControl for Daif {
+    type State = u64;
+
+    fn save_and_mask() -> u64 {
+        let daif: u64;
+        unsafe { asm!("mrs {daif}, daif", daif = out(reg) daif) };
+
+        let mask = DaifMask::from(Exception::from(Interrupt::Irq)).0
+            | DaifMask::from(Exception::from(Interrupt::Fiq)).0;
+        unsafe { asm!("msr daif, {daif}", daif = in(reg) daif | mask) };
+
+        daif
+    }
+
+    fn restore(daif: u64) {
+        unsafe { asm!("msr daif, {daif}", daif = in(reg) daif) };
+    }
+}
+
 /// Returns the current Exception Level.
 pub fn current_el() -> u64 {
     unsafe {
@@ -0,0 +1,638 @@
+//! CPU identification and feature reporting.
+//!
+//! The information is decoded from the identification registers described in
+//! the [Arm Architecture Reference Manual for A-profile architecture]. The
+//! [`CpuInfo`] type gathers all of them and implements [`fmt::Display`],
+//! which produces a summary suitable for boot logs.
+//!
+//! [Arm Architecture Reference Manual for A-profile architecture]: https://developer.arm.com/documentation/ddi0487/latest
+
+use core::arch::asm;
+use core::fmt;
+
+/// Maximum number of cache levels described by CLIDR_EL1.
+const MAX_CACHE_LEVELS: usize = 7;
+
+/// Main ID register (MIDR_EL1).
+#[derive(Debug, Copy, Clone)]
+pub struct Midr(u64);
+
+impl Midr {
+    /// Reads the MIDR_EL1 register of the current core.
+    pub fn read() -> Midr {
+        let mut midr_el1: u64;
+        unsafe { asm!("mrs {}, midr_el1", out(reg) midr_el1) };
+        Midr(midr_el1)
+    }
+
+    /// Returns the implementer code.
+    pub fn implementer(&self) -> u8 {
+        ((self.0 >> 24) & 0xff) as u8
+    }
+
+    /// Returns the name of the implementer if known.
+    pub fn implementer_name(&self) -> Option<&'static str> {
+        match self.implementer() {
+            0x41 => Some("Arm"),
+            0x42 => Some("Broadcom"),
+            0x43 => Some("Cavium"),
+            0x51 => Some("Qualcomm"),
+            0x61 => Some("Apple"),
+            _ => None,
+        }
+    }
+
+    /// Returns the variant number, which is the major revision of the
+    /// product.
+    pub fn variant(&self) -> u8 {
+        ((self.0 >> 20) & 0xf) as u8
+    }
+
+    /// Returns the primary part number.
+    pub fn part(&self) -> u16 {
+        ((self.0 >> 4) & 0xfff) as u16
+    }
+
+    /// Returns the name of the part if known.
+    pub fn part_name(&self) -> Option<&'static str> {
+        if self.implementer() != 0x41 {
+            return None;
+        }
+
+        match self.part() {
+            0xd03 => Some("Cortex-A53"),
+            0xd04 => Some("Cortex-A35"),
+            0xd05 => Some("Cortex-A55"),
+            0xd07 => Some("Cortex-A57"),
+            0xd08 => Some("Cortex-A72"),
+            0xd09 => Some("Cortex-A73"),
+            0xd0a => Some("Cortex-A75"),
+            0xd0b => Some("Cortex-A76"),
+            _ => None,
+        }
+    }
+
+    /// Returns the revision number, which is the minor revision of the
+    /// product.
+    pub fn revision(&self) -> u8 {
+        (self.0 & 0xf) as u8
+    }
+}
+
+impl fmt::Display for Midr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.implementer_name() {
+            Some(name) => write!(f, "{name} ")?,
+            None => write!(f, "implementer {:#x} ", self.implementer())?,
+        }
+
+        match self.part_name() {
+            Some(name) => write!(f, "{name} ")?,
+            None => write!(f, "part {:#x} ", self.part())?,
+        }
+
+        write!(f, "r{}p{}", self.variant(), self.revision())
+    }
+}
+
+/// Multiprocessor affinity register (MPIDR_EL1).
+#[derive(Debug, Copy, Clone)]
+pub struct Mpidr(u64);
+
+impl Mpidr {
+    /// Reads the MPIDR_EL1 register of the current core.
+    pub fn read() -> Mpidr {
+        let mut mpidr_el1: u64;
+        unsafe { asm!("mrs {}, mpidr_el1", out(reg) mpidr_el1) };
+        Mpidr(mpidr_el1)
+    }
+
+    /// Returns the affinity level `level`, which must be in the range [0, 3].
+    pub fn aff(&self, level: usize) -> u8 {
+        let shift = match level {
+            0 => 0,
+            1 => 8,
+            2 => 16,
+            3 => 32,
+            _ => panic!("invalid affinity level: {level}"),
+        };
+        ((self.0 >> shift) & 0xff) as u8
+    }
+
+    /// Returns true if the core is part of a uniprocessor system.
+    pub fn is_uniprocessor(&self) -> bool {
+        self.0 & (1 << 30) != 0
+    }
+
+    /// Returns true if the lowest affinity level consists of logical cores
+    /// implemented using a multithreading type approach.
+    pub fn is_multithreaded(&self) -> bool {
+        self.0 & (1 << 24) != 0
+    }
+}
+
+impl fmt::Display for Mpidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.aff(3),
+            self.aff(2),
+            self.aff(1),
+            self.aff(0),
+        )
+    }
+}
+
+/// Support of an Exception Level.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ElSupport {
+    /// The Exception Level is not implemented.
+    NotImplemented,
+
+    /// The Exception Level can be executed in AArch64 state only.
+    AArch64,
+
+    /// The Exception Level can be executed in either AArch64 or AArch32
+    /// state.
+    AArch64AArch32,
+}
+
+/// AArch64 processor feature register 0 (ID_AA64PFR0_EL1).
+#[derive(Debug, Copy, Clone)]
+pub struct Pfr0(u64);
+
+impl Pfr0 {
+    /// Reads the ID_AA64PFR0_EL1 register of the current core.
+    pub fn read() -> Pfr0 {
+        let mut id_aa64pfr0_el1: u64;
+        unsafe { asm!("mrs {}, id_aa64pfr0_el1", out(reg) id_aa64pfr0_el1) };
+        Pfr0(id_aa64pfr0_el1)
+    }
+
+    /// Returns the support of the Exception Level `el`, which must be in the
+    /// range [0, 3].
+    pub fn el(&self, el: usize) -> ElSupport {
+        assert!(el <= 3, "invalid exception level: {el}");
+        match (self.0 >> (el * 4)) & 0xf {
+            0b0001 => ElSupport::AArch64,
+            0b0010 => ElSupport::AArch64AArch32,
+            _ => ElSupport::NotImplemented,
+        }
+    }
+
+    /// Returns true if floating-point is implemented.
+    pub fn fp(&self) -> bool {
+        (self.0 >> 16) & 0xf != 0xf
+    }
+
+    /// Returns true if Advanced SIMD is implemented.
+    pub fn advsimd(&self) -> bool {
+        (self.0 >> 20) & 0xf != 0xf
+    }
+
+    /// Returns true if the System register interface to a GIC CPU interface
+    /// is implemented.
+    pub fn gic_sysregs(&self) -> bool {
+        (self.0 >> 24) & 0xf != 0
+    }
+}
+
+/// AArch64 instruction set attribute register 0 (ID_AA64ISAR0_EL1).
+#[derive(Debug, Copy, Clone)]
+pub struct Isar0(u64);
+
+impl Isar0 {
+    /// Reads the ID_AA64ISAR0_EL1 register of the current core.
+    pub fn read() -> Isar0 {
+        let mut isar0: u64;
+        unsafe { asm!("mrs {}, id_aa64isar0_el1", out(reg) isar0) };
+        Isar0(isar0)
+    }
+
+    /// Returns true if the AES instructions are implemented.
+    pub fn aes(&self) -> bool {
+        (self.0 >> 4) & 0xf != 0
+    }
+
+    /// Returns true if the PMULL/PMULL2 instructions are implemented.
+    pub fn pmull(&self) -> bool {
+        (self.0 >> 4) & 0xf >= 0b0010
+    }
+
+    /// Returns true if the SHA1 instructions are implemented.
+    pub fn sha1(&self) -> bool {
+        (self.0 >> 8) & 0xf != 0
+    }
+
+    /// Returns true if the SHA256 instructions are implemented.
+    pub fn sha2(&self) -> bool {
+        (self.0 >> 12) & 0xf != 0
+    }
+
+    /// Returns true if the CRC32 instructions are implemented.
+    pub fn crc32(&self) -> bool {
+        (self.0 >> 16) & 0xf != 0
+    }
+
+    /// Returns true if the Large System Extensions atomic instructions are
+    /// implemented.
+    pub fn atomic(&self) -> bool {
+        (self.0 >> 20) & 0xf != 0
+    }
+}
+
+/// AArch64 memory model feature register 0 (ID_AA64MMFR0_EL1).
+#[derive(Debug, Copy, Clone)]
+pub struct Mmfr0(u64);
+
+impl Mmfr0 {
+    /// Reads the ID_AA64MMFR0_EL1 register of the current core.
+    pub fn read() -> Mmfr0 {
+        let mut mmfr0: u64;
+        unsafe { asm!("mrs {}, id_aa64mmfr0_el1", out(reg) mmfr0) };
+        Mmfr0(mmfr0)
+    }
+
+    /// Returns the raw PARange field. Its encoding matches the one of the
+    /// PS/IPS fields of the TCR_ELx registers.
+    pub fn pa_range(&self) -> u8 {
+        (self.0 & 0xf) as u8
+    }
+
+    /// Returns the supported physical address size in bits.
+    pub fn pa_bits(&self) -> u32 {
+        match self.pa_range() {
+            0b0000 => 32,
+            0b0001 => 36,
+            0b0010 => 40,
+            0b0011 => 42,
+            0b0100 => 44,
+            0b0101 => 48,
+            _ => 52,
+        }
+    }
+
+    /// Returns the number of ASID bits.
+    pub fn asid_bits(&self) -> u32 {
+        if (self.0 >> 4) & 0xf == 0b0010 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Returns true if the 4KB translation granule is supported.
+    pub fn tgran4(&self) -> bool {
+        (self.0 >> 28) & 0xf != 0xf
+    }
+
+    /// Returns true if the 16KB translation granule is supported.
+    pub fn tgran16(&self) -> bool {
+        (self.0 >> 20) & 0xf != 0
+    }
+
+    /// Returns true if the 64KB translation granule is supported.
+    pub fn tgran64(&self) -> bool {
+        (self.0 >> 24) & 0xf != 0xf
+    }
+}
+
+/// Cache type register (CTR_EL0).
+#[derive(Debug, Copy, Clone)]
+pub struct Ctr(u64);
+
+impl Ctr {
+    /// Reads the CTR_EL0 register of the current core.
+    pub fn read() -> Ctr {
+        let mut ctr_el0: u64;
+        unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr_el0) };
+        Ctr(ctr_el0)
+    }
+
+    /// Returns the size in bytes of the smallest cache line of all the
+    /// instruction caches that are controlled by the PE.
+    pub fn icache_min_line_size(&self) -> usize {
+        // IminLine is the Log2 of the number of words.
+        4 << (self.0 & 0xf)
+    }
+
+    /// Returns the size in bytes of the smallest cache line of all the data
+    /// caches and unified caches that are controlled by the PE.
+    pub fn dcache_min_line_size(&self) -> usize {
+        // DminLine is the Log2 of the number of words.
+        4 << ((self.0 >> 16) & 0xf)
+    }
+
+    /// Returns the Cache Writeback Granule in bytes, which is the maximum size
+    /// of memory that can be overwritten as a result of the eviction of a
+    /// cache entry. `None` means that it is not provided.
+    pub fn cache_writeback_granule(&self) -> Option<usize> {
+        match (self.0 >> 24) & 0xf {
+            0 => None,
+            cwg => Some(4 << cwg),
+        }
+    }
+}
+
+/// Type of a cache.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CacheKind {
+    /// Instruction cache.
+    Instruction,
+
+    /// Data cache.
+    Data,
+
+    /// Unified cache.
+    Unified,
+}
+
+impl fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheKind::Instruction => write!(f, "instruction"),
+            CacheKind::Data => write!(f, "data"),
+            CacheKind::Unified => write!(f, "unified"),
+        }
+    }
+}
+
+/// Geometry of a cache.
+#[derive(Debug, Copy, Clone)]
+pub struct Cache {
+    /// Cache level, starting at 1.
+    level: usize,
+
+    /// Type of cache.
+    kind: CacheKind,
+
+    /// Line size in bytes.
+    line_size: usize,
+
+    /// Associativity.
+    ways: usize,
+
+    /// Number of sets.
+    sets: usize,
+}
+
+impl Cache {
+    /// Reads the geometry of a cache from CCSIDR_EL1. `level` starts at 1.
+    fn read(level: usize, kind: CacheKind) -> Cache {
+        let ind = match kind {
+            CacheKind::Instruction => 1,
+            CacheKind::Data | CacheKind::Unified => 0,
+        };
+        let csselr_el1 = (((level - 1) << 1) | ind) as u64;
+
+        let mut ccsidr_el1: u64;
+        unsafe {
+            asm!(
+                r#"
+                    msr csselr_el1, {csselr_el1}
+                    isb
+                    mrs {ccsidr_el1}, ccsidr_el1
+                "#,
+                csselr_el1 = in(reg) csselr_el1,
+                ccsidr_el1 = out(reg) ccsidr_el1,
+            );
+        }
+
+        Cache {
+            level,
+            kind,
+            line_size: 16 << (ccsidr_el1 & 0x7),
+            ways: (((ccsidr_el1 >> 3) & 0x3ff) + 1) as usize,
+            sets: (((ccsidr_el1 >> 13) & 0x7fff) + 1) as usize,
+        }
+    }
+
+    /// Returns the cache level, starting at 1.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Returns the type of cache.
+    pub fn kind(&self) -> CacheKind {
+        self.kind
+    }
+
+    /// Returns the line size in bytes.
+    pub fn line_size(&self) -> usize {
+        self.line_size
+    }
+
+    /// Returns the associativity of the cache.
+    pub fn ways(&self) -> usize {
+        self.ways
+    }
+
+    /// Returns the number of sets.
+    pub fn sets(&self) -> usize {
+        self.sets
+    }
+
+    /// Returns the size of the cache in bytes.
+    pub fn size(&self) -> usize {
+        self.line_size * self.ways * self.sets
+    }
+}
+
+impl fmt::Display for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "L{} {}: {} KiB, {}-way, {} sets, {}-byte lines",
+            self.level,
+            self.kind,
+            self.size() / 1024,
+            self.ways,
+            self.sets,
+            self.line_size,
+        )
+    }
+}
+
+/// Cache hierarchy described by CLIDR_EL1.
+#[derive(Debug, Copy, Clone)]
+pub struct Caches {
+    /// Caches. Every level can have separate instruction and data caches.
+    caches: [Option<Cache>; 2 * MAX_CACHE_LEVELS],
+
+    /// Number of caches.
+    len: usize,
+}
+
+impl Caches {
+    /// Reads the cache hierarchy of the current core.
+    pub fn read() -> Caches {
+        let mut clidr_el1: u64;
+        unsafe { asm!("mrs {}, clidr_el1", out(reg) clidr_el1) };
+
+        let mut caches = Caches {
+            caches: [None; 2 * MAX_CACHE_LEVELS],
+            len: 0,
+        };
+
+        for level in 1..=MAX_CACHE_LEVELS {
+            let kinds: &[CacheKind] = match (clidr_el1 >> ((level - 1) * 3)) & 7
+            {
+                0b001 => &[CacheKind::Instruction],
+                0b010 => &[CacheKind::Data],
+                0b011 => &[CacheKind::Data, CacheKind::Instruction],
+                0b100 => &[CacheKind::Unified],
+                // No more caches.
+                _ => break,
+            };
+
+            for kind in kinds {
+                caches.caches[caches.len] = Some(Cache::read(level, *kind));
+                caches.len += 1;
+            }
+        }
+
+        caches
+    }
+
+    /// Returns an iterator over the caches.
+    pub fn iter(&self) -> impl Iterator<Item = &Cache> {
+        self.caches[..self.len].iter().flatten()
+    }
+}
+
+/// Identification and features of the current core.
+#[derive(Debug, Copy, Clone)]
+pub struct CpuInfo {
+    /// Main ID register.
+    midr: Midr,
+
+    /// Multiprocessor affinity register.
+    mpidr: Mpidr,
+
+    /// Processor feature register 0.
+    pfr0: Pfr0,
+
+    /// Instruction set attribute register 0.
+    isar0: Isar0,
+
+    /// Memory model feature register 0.
+    mmfr0: Mmfr0,
+
+    /// Cache type register.
+    ctr: Ctr,
+
+    /// Cache hierarchy.
+    caches: Caches,
+}
+
+impl CpuInfo {
+    /// Reads the identification registers of the current core.
+    pub fn read() -> CpuInfo {
+        CpuInfo {
+            midr: Midr::read(),
+            mpidr: Mpidr::read(),
+            pfr0: Pfr0::read(),
+            isar0: Isar0::read(),
+            mmfr0: Mmfr0::read(),
+            ctr: Ctr::read(),
+            caches: Caches::read(),
+        }
+    }
+
+    /// Returns the main ID register.
+    pub fn midr(&self) -> Midr {
+        self.midr
+    }
+
+    /// Returns the multiprocessor affinity register.
+    pub fn mpidr(&self) -> Mpidr {
+        self.mpidr
+    }
+
+    /// Returns the processor feature register 0.
+    pub fn pfr0(&self) -> Pfr0 {
+        self.pfr0
+    }
+
+    /// Returns the instruction set attribute register 0.
+    pub fn isar0(&self) -> Isar0 {
+        self.isar0
+    }
+
+    /// Returns the memory model feature register 0.
+    pub fn mmfr0(&self) -> Mmfr0 {
+        self.mmfr0
+    }
+
+    /// Returns the cache type register.
+    pub fn ctr(&self) -> Ctr {
+        self.ctr
+    }
+
+    /// Returns the cache hierarchy.
+    pub fn caches(&self) -> &Caches {
+        &self.caches
+    }
+}
+
+impl fmt::Display for CpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "CPU: {} (affinity {})", self.midr, self.mpidr)?;
+
+        write!(f, "Exception levels:")?;
+        for el in 0..=3 {
+            match self.pfr0.el(el) {
+                ElSupport::NotImplemented => {}
+                ElSupport::AArch64 => write!(f, " EL{el}")?,
+                ElSupport::AArch64AArch32 => write!(f, " EL{el}(+AArch32)")?,
+            }
+        }
+        writeln!(f)?;
+
+        write!(f, "Features:")?;
+        let features = [
+            ("fp", self.pfr0.fp()),
+            ("asimd", self.pfr0.advsimd()),
+            ("aes", self.isar0.aes()),
+            ("pmull", self.isar0.pmull()),
+            ("sha1", self.isar0.sha1()),
+            ("sha2", self.isar0.sha2()),
+            ("crc32", self.isar0.crc32()),
+            ("atomics", self.isar0.atomic()),
+        ];
+        for (name, _) in features.iter().filter(|(_, present)| *present) {
+            write!(f, " {name}")?;
+        }
+        writeln!(f)?;
+
+        write!(
+            f,
+            "Memory: {}-bit PA, {}-bit ASID, granules:",
+            self.mmfr0.pa_bits(),
+            self.mmfr0.asid_bits(),
+        )?;
+        let granules = [
+            ("4KB", self.mmfr0.tgran4()),
+            ("16KB", self.mmfr0.tgran16()),
+            ("64KB", self.mmfr0.tgran64()),
+        ];
+        for (name, _) in granules.iter().filter(|(_, present)| *present) {
+            write!(f, " {name}")?;
+        }
+        writeln!(f)?;
+
+        write!(
+            f,
+            "Cache lines: {}-byte I-cache, {}-byte D-cache",
+            self.ctr.icache_min_line_size(),
+            self.ctr.dcache_min_line_size(),
+        )?;
+
+        for cache in self.caches.iter() {
+            write!(f, "\n{cache}")?;
+        }
+
+        Ok(())
+    }
+}
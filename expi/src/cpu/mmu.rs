@@ -2,6 +2,11 @@
 
 use core::arch::asm;
 
+use crate::cpu::info::{Ctr, Mmfr0};
+use crate::cpu::{mp, Error};
+
+use range::Range;
+
 /// Represents a page table.
 #[repr(C, align(0x1000))]
 struct PageTable([u64; 512]);
@@ -25,9 +30,88 @@ const TMPL_DEV_NGNRNE: u64 = (1 << 54) | (1 << 53) | (1 << 10) | (2 << 2);
 /// Template for normal cacheable memory attributes: AF=1 SH=3 (inner) Indx=1.
 const TMPL_NORMAL_WBWARA: u64 = (1 << 10) | (3 << 8) | (1 << 2);
 
+/// Size in bytes of the blocks mapped by the level 2 page table.
+pub const L2_BLOCK_SIZE: u64 = 0x20_0000;
+
+/// Encoding of a 48-bit physical address size in the PS field of TCR_EL2.
+const TCR_PS_48BIT: u8 = 0b101;
+
 /// Configure the MMU for identity mapping.
-#[allow(static_mut_refs)]
+///
+/// This function must be called by the boot core. It tries to enable SMP
+/// coherency, fills the page tables and turns on the MMU. Secondary cores
+/// must use [`enable_identity_mapping_secondary`], which shares the page
+/// tables built here.
 pub fn enable_identity_mapping() {
+    // SMPEN must be set before enabling the MMU and the caches. A failure is
+    // not fatal here: single-core kernels do not need SMP coherency and the
+    // UART is not initialized yet, so it could not be reported. It is checked
+    // again by `globals::init`, which reports it, and by
+    // `mp::release_secondaries`, which requires it.
+    let _ = mp::enable_smp_coherency();
+
+    fill_page_tables();
+    enable_mmu();
+}
+
+/// Configure the MMU of a secondary core for identity mapping.
+///
+/// The page tables are not filled again. Given that the translation table
+/// walks are configured as inner shareable and cacheable, and that SMP
+/// coherency is enabled before turning on the MMU, the secondary core observes
+/// the page tables written by the boot core without any cache maintenance.
+///
+/// # Safety
+///
+/// The page tables must have been filled by the boot core via
+/// [`enable_identity_mapping`] and SMP coherency must be enabled in the
+/// current core before calling this function. See [`mp::init_secondary`].
+pub unsafe fn enable_identity_mapping_secondary() {
+    enable_mmu();
+}
+
+/// Fills the identity mapping page tables.
+#[allow(static_mut_refs)]
+fn fill_page_tables() {
+    unsafe {
+        // Level 1: 1GB entries.
+
+        // 0x0000_0000-0x3fff_ffff.
+        PAGE_TABLE_L1.0[0] =
+            PAGE_TABLE_L2_00000000_3FFFFFFF.0.as_ptr() as u64 | 3;
+        // 0x4000_0000-0x7fff_ffff. Peripherals (device memory).
+        PAGE_TABLE_L1.0[1] = TMPL_DEV_NGNRNE | 1 | 0x4000_0000;
+        // 0x8000_0000-0xbfff_ffff. Peripherals (device memory).
+        PAGE_TABLE_L1.0[2] = TMPL_DEV_NGNRNE | 1 | 0x8000_0000;
+        // 0xc000_0000-0xffff_ffff. Peripherals (device memory).
+        PAGE_TABLE_L1.0[3] = TMPL_DEV_NGNRNE | 1 | 0xc000_0000;
+
+        // Level 2: 2MB entries.
+
+        // 0x0000_0000-0x3fff_ffff. Identity mapping.
+        for (i, entry) in
+            PAGE_TABLE_L2_00000000_3FFFFFFF.0.iter_mut().enumerate()
+        {
+            let baddr = i as u64 * L2_BLOCK_SIZE;
+            *entry = match baddr {
+                // ARM memory (normal memory, cacheable).
+                ..=0x3bff_ffff => TMPL_NORMAL_WBWARA | 1 | baddr,
+                // VC memory (not used by expi).
+                0x3c00_0000..=0x3eff_ffff => 0,
+                // VC memory (device memory).
+                0x3f00_0000.. => TMPL_DEV_NGNRNE | 1 | baddr,
+            };
+        }
+
+        // Ensure the page tables are written before the MMU is enabled.
+        asm!("dsb sy");
+    }
+}
+
+/// Configures the translation regime of the current core using the identity
+/// mapping page tables and enables the MMU.
+#[allow(static_mut_refs)]
+fn enable_mmu() {
     // Set l1 page table base address.
     let ttbr0_el2 = unsafe { PAGE_TABLE_L1.0.as_ptr() as u64 };
     unsafe { asm!( "msr ttbr0_el2, {}", in(reg) ttbr0_el2) };
@@ -49,7 +133,9 @@ pub fn enable_identity_mapping() {
     tcr_el2 |= 3 << 12;
     // TBI = 0: Top byte not ignored.
     // TG0 = 0: 4KB Granule.
-    // IPS = 0: 32-bit IPA space.
+    // PS: Physical address size supported by the core. 52-bit addresses
+    // require FEAT_LPA2 with a 4KB granule, so the size is limited to 48 bits.
+    tcr_el2 |= (Mmfr0::read().pa_range().min(TCR_PS_48BIT) as u64) << 16;
     unsafe { asm!("msr tcr_el2, {}", in(reg) tcr_el2) };
 
     // Ensure changes to system registers are visible before MMU is enabled.
@@ -66,40 +152,6 @@ pub fn enable_identity_mapping() {
         );
     }
 
-    // Fill page tables.
-    unsafe {
-        // Level 1: 1GB entries.
-
-        // 0x0000_0000-0x3fff_ffff.
-        PAGE_TABLE_L1.0[0] =
-            PAGE_TABLE_L2_00000000_3FFFFFFF.0.as_ptr() as u64 | 3;
-        // 0x4000_0000-0x7fff_ffff. Peripherals (device memory).
-        PAGE_TABLE_L1.0[1] = TMPL_DEV_NGNRNE | 1 | 0x4000_0000;
-        // 0x8000_0000-0xbfff_ffff. Peripherals (device memory).
-        PAGE_TABLE_L1.0[2] = TMPL_DEV_NGNRNE | 1 | 0x8000_0000;
-        // 0xc000_0000-0xffff_ffff. Peripherals (device memory).
-        PAGE_TABLE_L1.0[3] = TMPL_DEV_NGNRNE | 1 | 0xc000_0000;
-
-        // Level 2: 2MB entries.
-
-        // 0x0000_0000-0x3fff_ffff. Identity mapping.
-        for (i, entry) in
-            PAGE_TABLE_L2_00000000_3FFFFFFF.0.iter_mut().enumerate()
-        {
-            let baddr = (i * 0x20_0000) as u64;
-            *entry = match baddr {
-                // ARM memory (normal memory, cacheable).
-                ..=0x3bff_ffff => TMPL_NORMAL_WBWARA | 1 | baddr,
-                // VC memory (not used by expi).
-                0x3c00_0000..=0x3eff_ffff => 0,
-                // VC memory (device memory).
-                0x3f00_0000.. => TMPL_DEV_NGNRNE | 1 | baddr,
-            };
-        }
-
-        asm!("dsb sy");
-    }
-
     // Enable MMU (M).
     let mut sctlr_el2: u64 = 1;
     // Enable data and unified caches (C).
@@ -118,18 +170,51 @@ pub fn enable_identity_mapping() {
     }
 }
 
+/// Removes the identity mapping of `range`. The identity mapping is built
+/// with 2MB blocks, so `range` must be made of whole blocks. Only the blocks
+/// of the first GB, which holds the ARM memory, can be unmapped.
+///
+/// The TLB entries are invalidated in the Inner Shareable domain, so the
+/// change is observed by every core.
+///
+/// # Safety
+///
+/// The memory in `range` must not be accessed afterwards.
+#[allow(static_mut_refs)]
+pub unsafe fn unmap_identity(range: Range) -> Result<(), Error> {
+    let entries = unsafe { &mut PAGE_TABLE_L2_00000000_3FFFFFFF.0 };
+
+    let mask = L2_BLOCK_SIZE - 1;
+    let limit = entries.len() as u64 * L2_BLOCK_SIZE;
+    if range.start() & mask != 0
+        || range.end() & mask != mask
+        || range.end() >= limit
+    {
+        return Err(Error::InvalidUnmapRange(range));
+    }
+
+    let first = (range.start() / L2_BLOCK_SIZE) as usize;
+    let last = (range.end() / L2_BLOCK_SIZE) as usize;
+    entries[first..=last].fill(0);
+
+    unsafe {
+        asm!(
+            r#"
+                dsb ishst
+                tlbi alle2is
+                dsb ish
+                isb
+            "#
+        );
+    }
+
+    Ok(())
+}
+
 /// Returns the size of the smallest cache line of all the data caches and
 /// unified caches.
 pub fn data_line_size() -> usize {
-    // Get DminLine, which is the Log2 of the number of words in the smallest
-    // cache line of all the data caches and unified caches that are controlled
-    // by the PE.
-    let mut ctr_el0: u64;
-    unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr_el0) };
-    let dminline = (ctr_el0 >> 16) & 0xf;
-
-    // Calculate the line size.
-    (4 << dminline) as usize
+    Ctr::read().dcache_min_line_size()
 }
 
 /// Cleans and invalidates the data cache for a virtual memory region to Point
@@ -1,17 +1,236 @@
 //! Multi-processing operations.
+//!
+//! # Secondary core bring-up
+//!
+//! On boot, the secondary cores are parked by the firmware, waiting for an
+//! event. Once the event is received, each core reads its release address,
+//! advertised by the `cpu-release-addr` property of its devicetree node, and,
+//! if not zero, jumps to the address stored there. The cores are released
+//! with the MMU and the caches disabled. See the firmware implementation in
+//! [armstub8.S]. The bring-up sequence is the following:
+//!
+//! 1. The boot core enables SMP coherency, fills the page tables and enables
+//!    its MMU (see [`mmu::enable_identity_mapping`]).
+//! 2. The boot core writes the entrypoint of the secondary cores into their
+//!    release addresses, cleans them to the Point of Coherency and sends an
+//!    event (see [`release_secondaries`]).
+//! 3. Every secondary core enables SMP coherency and its MMU, sharing the page
+//!    tables built by the boot core (see [`init_secondary`]).
+//!
+//! After step 3, all the cores are in the same inner shareable domain with
+//! coherent data caches. Thus, data shared between cores is visible without
+//! any cache maintenance.
+//!
+//! [armstub8.S]: https://github.com/raspberrypi/tools/blob/master/armstubs/armstub8.S
 
 use core::arch::asm;
+use core::ptr;
 
-use crate::cpu::Core;
+use crate::cpu::{mmu, Core, Error};
+use crate::globals::GLOBALS;
+use crate::mm::{BOOT_STACK_BASE, KERNEL_BASE};
+
+/// SMPEN bit of the CPUECTLR_EL1 register.
+///
+/// Enables the receiving of instruction cache, BTB, and TLB maintenance
+/// operations broadcast from other cores in the cluster. It also enables the
+/// data coherency with the other cores.
+const CPUECTLR_EL1_SMPEN: u64 = 1 << 6;
+
+/// U bit of the MPIDR_EL1 register. If set, the core is part of a
+/// uniprocessor system.
+const MPIDR_EL1_U: u64 = 1 << 30;
+
+/// Mask of the affinity fields (Aff3, Aff2, Aff1 and Aff0) of the MPIDR_EL1
+/// register.
+const MPIDR_EL1_AFF_MASK: u64 = 0xff_00ff_ffff;
+
+/// Size in bytes of the temporary stack used by a secondary core during its
+/// bring-up, before it can look up its core number in the [`Topology`].
+///
+/// The MMU is disabled at that point, so the stacks are indexed by the Aff0
+/// field of MPIDR_EL1 and placed right below [`KERNEL_BASE`]. See
+/// `expi_macros::entrypoint_mp`.
+///
+/// [`Topology`]: crate::cpu::topology::Topology
+pub const SECONDARY_BOOT_STACK_SIZE: u64 = 0x10000;
 
 /// Returns the ID of the current core.
 pub fn core_id() -> u8 {
+    (affinity() & 0xff) as u8
+}
+
+/// Returns the affinity fields of the MPIDR_EL1 register of the current core.
+///
+/// The returned value matches the `reg` property of the corresponding CPU
+/// node in the devicetree.
+pub fn affinity() -> u64 {
     let mut mpidr_el1: u64;
     unsafe { asm!("mrs {}, mpidr_el1", out(reg) mpidr_el1) };
-    (mpidr_el1 & 0xff) as u8
+    mpidr_el1 & MPIDR_EL1_AFF_MASK
 }
 
 /// Returns the current core.
 pub fn core() -> Core {
-    Core::try_from(core_id()).expect("invalid core")
+    let topology = GLOBALS.topology().get().expect("uninitialized topology");
+    let core = topology.core_number(affinity()).expect("unknown core");
+    Core(core)
+}
+
+/// Returns an error if the current core is part of a uniprocessor system.
+fn check_multiprocessor() -> Result<(), Error> {
+    let mut mpidr_el1: u64;
+    unsafe { asm!("mrs {}, mpidr_el1", out(reg) mpidr_el1) };
+    if mpidr_el1 & MPIDR_EL1_U != 0 {
+        return Err(Error::Uniprocessor);
+    }
+    Ok(())
+}
+
+/// Returns the value of the CPUECTLR_EL1 register of the current core.
+fn cpuectlr_el1() -> u64 {
+    let mut cpuectlr_el1: u64;
+    unsafe {
+        asm!(
+            "mrs {cpuectlr_el1}, s3_1_c15_c2_1",
+            cpuectlr_el1 = out(reg) cpuectlr_el1,
+        );
+    }
+    cpuectlr_el1
+}
+
+/// Enables SMP coherency in the current core.
+///
+/// It sets CPUECTLR_EL1.SMPEN, which must be done before enabling the MMU and
+/// the data caches. Then, it checks that the change has taken effect. See
+/// [`check_smp_coherency`].
+///
+/// The firmware grants EL2 access to CPUECTLR_EL1 via ACTLR_EL3 and
+/// ACTLR_EL2.
+pub fn enable_smp_coherency() -> Result<(), Error> {
+    check_multiprocessor()?;
+
+    let cpuectlr_el1 = cpuectlr_el1();
+    if cpuectlr_el1 & CPUECTLR_EL1_SMPEN == 0 {
+        unsafe {
+            asm!(
+                r#"
+                    msr s3_1_c15_c2_1, {cpuectlr_el1}
+                    isb
+                "#,
+                cpuectlr_el1 = in(reg) cpuectlr_el1 | CPUECTLR_EL1_SMPEN,
+            );
+        }
+    }
+
+    check_smp_coherency()
+}
+
+/// Checks that SMP coherency is enabled in the current core without trying
+/// to enable it.
+///
+/// Some emulators model CPUECTLR_EL1 as a constant zero that ignores writes,
+/// so SMP coherency may be disabled even after calling
+/// [`enable_smp_coherency`]. This only matters when several cores share
+/// data, so single-core kernels can ignore the error.
+pub fn check_smp_coherency() -> Result<(), Error> {
+    check_multiprocessor()?;
+
+    if cpuectlr_el1() & CPUECTLR_EL1_SMPEN == 0 {
+        return Err(Error::SmpCoherencyDisabled);
+    }
+
+    Ok(())
+}
+
+/// Releases the secondary cores, which will jump to `entry`.
+///
+/// The secondary cores are the CPUs in the [`Topology`] but the current one.
+/// They must be enabled via a spin-table. The entrypoint is written into
+/// their release addresses, which are read by the secondary cores with the
+/// MMU and the caches disabled. So, the release addresses are cleaned to the
+/// Point of Coherency after being written. Finally, an event is sent to wake
+/// up the cores.
+///
+/// Every store done by the current core before calling this function is
+/// visible to the secondary cores once they have called [`init_secondary`].
+///
+/// It fails if SMP coherency is not enabled in the current core. See
+/// [`check_smp_coherency`].
+///
+/// # Safety
+///
+/// `entry` must be the address of code prepared to run with the MMU disabled
+/// and it must call [`init_secondary`] before accessing shared data.
+///
+/// [`Topology`]: crate::cpu::topology::Topology
+pub unsafe fn release_secondaries(entry: usize) -> Result<(), Error> {
+    // The boot core must be coherent with the cores it is about to release.
+    check_smp_coherency()?;
+
+    let topology = GLOBALS.topology().get().ok_or(Error::Uninitialized)?;
+
+    let boot_affinity = affinity();
+
+    // Check every core before releasing any of them.
+    for cpu in topology.cpus() {
+        if cpu.reg() != boot_affinity {
+            check_boot_stack(cpu.reg())?;
+        }
+    }
+
+    for cpu in topology.cpus() {
+        if cpu.reg() == boot_affinity {
+            continue;
+        }
+
+        let release_addr = cpu
+            .release_addr()
+            .ok_or(Error::UnsupportedEnableMethod(cpu.reg()))?
+            as usize;
+
+        unsafe {
+            ptr::write_volatile(release_addr as *mut u64, entry as u64);
+            mmu::dcache_clean_inval_poc(release_addr, 8);
+        }
+    }
+
+    // `dcache_clean_inval_poc` finishes with a DSB. Thus, all previous memory
+    // accesses have completed at this point.
+    unsafe { asm!("sev") };
+
+    Ok(())
+}
+
+/// Returns an error if the temporary stack of the secondary core with the
+/// provided affinity does not fit in the boot stacks region. See
+/// [`SECONDARY_BOOT_STACK_SIZE`].
+///
+/// The stacks are indexed by Aff0, so cores in other clusters are rejected
+/// too. Otherwise, they would share a stack with the cores of the first one.
+fn check_boot_stack(affinity: u64) -> Result<(), Error> {
+    let aff0 = affinity & 0xff;
+    let bottom = (aff0 + 2)
+        .checked_mul(SECONDARY_BOOT_STACK_SIZE)
+        .and_then(|offset| KERNEL_BASE.checked_sub(offset));
+
+    if affinity != aff0 || bottom.is_none_or(|b| b < BOOT_STACK_BASE) {
+        return Err(Error::NoBootStack(affinity));
+    }
+
+    Ok(())
+}
+
+/// Brings up the current secondary core.
+///
+/// It enables SMP coherency and the MMU, reusing the page tables built by the
+/// boot core.
+///
+/// # Safety
+///
+/// This function must only be called by secondary cores released via
+/// [`release_secondaries`].
+pub unsafe fn init_secondary() {
+    enable_smp_coherency().expect("could not enable SMP coherency");
+    unsafe { mmu::enable_identity_mapping_secondary() };
 }
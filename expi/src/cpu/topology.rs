@@ -0,0 +1,164 @@
+//! CPU topology.
+//!
+//! The list of CPUs is read from the `/cpus` node of the devicetree. For
+//! instance, [/arch/arm/boot/dts/bcm2837.dtsi] describes the CPUs of the
+//! Raspberry Pi 3 Model B:
+//!
+//! ```text
+//! cpus: cpus {
+//!     #address-cells = <1>;
+//!     #size-cells = <0>;
+//!     enable-method = "brcm,bcm2836-smp"; // for ARM 32-bit
+//!
+//!     cpu0: cpu@0 {
+//!         device_type = "cpu";
+//!         compatible = "arm,cortex-a53";
+//!         reg = <0>;
+//!         enable-method = "spin-table";
+//!         cpu-release-addr = <0x0 0x000000d8>;
+//!     };
+//!     ...
+//! };
+//! ```
+//!
+//! [/arch/arm/boot/dts/bcm2837.dtsi]: https://github.com/raspberrypi/linux/blob/770d94882ac145c81af72e9a37180806c3f70bbd/arch/arm/boot/dts/bcm2837.dtsi#L38-L76
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cpu::Error;
+use crate::fdt::property::Reg;
+use crate::fdt::Fdt;
+use crate::globals::GLOBALS;
+
+/// Method used to enable a CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnableMethod {
+    /// The CPU waits for an event and, then, jumps to the address stored at
+    /// its release address if not zero.
+    SpinTable {
+        /// Release address.
+        release_addr: u64,
+    },
+
+    /// Unsupported enable method.
+    Other(String),
+}
+
+/// Represents a CPU described in the devicetree.
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    /// Value of the `reg` property. It matches the affinity fields of the
+    /// MPIDR_EL1 register of the CPU.
+    reg: u64,
+
+    /// Method used to enable the CPU.
+    enable_method: EnableMethod,
+}
+
+impl Cpu {
+    /// Returns the value of the `reg` property of the CPU, which matches the
+    /// affinity fields of its MPIDR_EL1 register.
+    pub fn reg(&self) -> u64 {
+        self.reg
+    }
+
+    /// Returns the method used to enable the CPU.
+    pub fn enable_method(&self) -> &EnableMethod {
+        &self.enable_method
+    }
+
+    /// Returns the release address of the CPU if it is enabled via a
+    /// spin-table.
+    pub fn release_addr(&self) -> Option<u64> {
+        match self.enable_method {
+            EnableMethod::SpinTable { release_addr } => Some(release_addr),
+            EnableMethod::Other(_) => None,
+        }
+    }
+}
+
+/// CPU topology.
+///
+/// The CPUs are sorted by their `reg` property. The position of a CPU in the
+/// list is its core number. See [`Core`](crate::cpu::Core).
+#[derive(Debug)]
+pub struct Topology {
+    /// CPUs described in the devicetree.
+    cpus: Vec<Cpu>,
+}
+
+impl Topology {
+    /// Reads the CPU topology from the `/cpus` node of the provided
+    /// [`Fdt`].
+    pub fn from_fdt(fdt: &Fdt) -> Result<Topology, Error> {
+        let cpus_node = fdt.structure_block().node("/cpus")?;
+        let address_cells = cpus_node.property("#address-cells")?.to_u32()?;
+        let size_cells = cpus_node.property("#size-cells")?.to_u32()?;
+
+        let mut cpus = Vec::new();
+        for node in cpus_node.children().values() {
+            match node.property("device_type") {
+                Ok(device_type) if device_type.to_string()? == "cpu" => {}
+                _ => continue,
+            }
+
+            let reg = node.property("reg")?;
+            let reg = Reg::new(reg, address_cells, size_cells)
+                .entries()
+                .next()
+                .ok_or(Error::MissingCpuReg)??
+                .0 as u64;
+
+            let enable_method = match node.property("enable-method") {
+                Ok(method) if method.to_string()? == "spin-table" => {
+                    let release_addr =
+                        node.property("cpu-release-addr")?.to_u64()?;
+                    EnableMethod::SpinTable { release_addr }
+                }
+                Ok(method) => EnableMethod::Other(method.to_string()?),
+                Err(_) => EnableMethod::Other(String::new()),
+            };
+
+            cpus.push(Cpu { reg, enable_method });
+        }
+
+        if cpus.is_empty() {
+            return Err(Error::NoCpus);
+        }
+
+        cpus.sort_by_key(|cpu| cpu.reg);
+
+        Ok(Topology { cpus })
+    }
+
+    /// Returns the CPUs described in the devicetree.
+    pub fn cpus(&self) -> &[Cpu] {
+        &self.cpus
+    }
+
+    /// Returns the number of CPU cores.
+    pub fn ncores(&self) -> usize {
+        self.cpus.len()
+    }
+
+    /// Returns the core number of the CPU with the provided affinity.
+    pub fn core_number(&self, affinity: u64) -> Option<usize> {
+        self.cpus.iter().position(|cpu| cpu.reg == affinity)
+    }
+}
+
+/// Initializes the global CPU topology.
+pub fn init() -> Result<(), Error> {
+    let fdt = GLOBALS.fdt().get().ok_or(Error::Uninitialized)?;
+    GLOBALS
+        .topology()
+        .try_call_once(|| Topology::from_fdt(fdt))?;
+    Ok(())
+}
+
+/// Returns the number of CPU cores.
+pub fn ncores() -> Result<usize, Error> {
+    let topology = GLOBALS.topology().get().ok_or(Error::Uninitialized)?;
+    Ok(topology.ncores())
+}
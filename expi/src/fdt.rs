@@ -9,6 +9,7 @@ use core::array::TryFromSliceError;
 use core::ffi::{CStr, FromBytesWithNulError};
 use core::fmt;
 use core::iter::FusedIterator;
+use core::marker::PhantomData;
 use core::num::TryFromIntError;
 use core::slice;
 use core::str::Utf8Error;
@@ -845,6 +846,13 @@ impl EarlyFdt {
         let root_ptr = self.header.ptr + (self.header.off_dt_struct as usize);
         NodePtrs::new(FdtPtr(root_ptr))
     }
+
+    /// Returns an iterator over the children of the provided node.
+    ///
+    /// Like [`EarlyFdt::node`], it does not allocate memory.
+    pub fn children(&self, node_ptr: FdtPtr) -> ChildPtrs<'_> {
+        ChildPtrs::new(node_ptr)
+    }
 }
 
 /// Represents a Flattened Devicetree.
@@ -1044,6 +1052,98 @@ impl Iterator for NodePtrs {
 
 impl FusedIterator for NodePtrs {}
 
+/// Iterator over the children of a node of an [`EarlyFdt`].
+///
+/// It yields a `Result` with the tuple `(name, pointer)` for every child.
+/// After an error, all successive calls will yield `None`.
+pub struct ChildPtrs<'a> {
+    /// Current position.
+    pos: usize,
+
+    /// Depth of the current position relative to the parent node.
+    level: i32,
+
+    /// If `done` is true, the `Iterator` has finished.
+    done: bool,
+
+    /// The names of the children are borrowed from the FDT.
+    _fdt: PhantomData<&'a EarlyFdt>,
+}
+
+impl<'a> ChildPtrs<'a> {
+    /// Creates an iterator over the children of the node at `node_ptr`.
+    fn new(node_ptr: FdtPtr) -> ChildPtrs<'a> {
+        ChildPtrs {
+            pos: node_ptr.0,
+            level: 0,
+            done: false,
+            _fdt: PhantomData,
+        }
+    }
+
+    /// Executes a new iteration. It is called by `Iterator::next`.
+    fn iter_next(&mut self) -> Result<Option<(&'a str, FdtPtr)>, Error> {
+        let mut mr = MemReader::new(self.pos);
+
+        loop {
+            let token = unsafe { mr.read_be::<u32>()? };
+            match token.into() {
+                Token::BeginNode => {
+                    self.level += 1;
+
+                    let name = unsafe { &*mr.read_cstr()? };
+                    // Skip padding.
+                    mr.set_position((mr.position() + 3) & !3);
+
+                    if self.level == 1 {
+                        self.pos = mr.position();
+                        break Ok(Some((name, FdtPtr(self.pos))));
+                    }
+                }
+                Token::EndNode => {
+                    self.level -= 1;
+
+                    if self.level < 0 {
+                        // The parent node ended.
+                        break Ok(None);
+                    }
+                }
+                Token::Prop => {
+                    let len = unsafe { mr.read_be::<u32>()? as usize };
+                    // Skip name offset (4), property value (len) and
+                    // padding.
+                    mr.skip((4 + len + 3) & !3);
+                }
+                Token::Nop => {}
+                Token::End => break Err(Error::MalformedStructureBlock),
+                Token::Unknown => break Err(Error::UnknownToken(token)),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ChildPtrs<'a> {
+    type Item = Result<(&'a str, FdtPtr), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let retval = self.iter_next();
+
+        self.done = match retval {
+            Ok(Some(_)) => false,
+            Ok(None) => true,
+            Err(_) => true,
+        };
+
+        retval.transpose()
+    }
+}
+
+impl FusedIterator for ChildPtrs<'_> {}
+
 impl IntoIterator for &EarlyFdt {
     type Item = Result<FdtPtr, Error>;
     type IntoIter = NodePtrs;
@@ -1055,14 +1155,8 @@ impl IntoIterator for &EarlyFdt {
 
 /// Initializes the global FDT.
 pub fn init(fdt_ptr32: u32) -> Result<(), Error> {
-    let mut fdt_mg = GLOBALS.fdt().lock();
-    if fdt_mg.is_some() {
-        // Already initialized.
-        return Ok(());
-    }
-
-    let fdt = unsafe { Fdt::parse(fdt_ptr32 as usize)? };
-    *fdt_mg = Some(fdt);
-
+    GLOBALS
+        .fdt()
+        .try_call_once(|| unsafe { Fdt::parse(fdt_ptr32 as usize) })?;
     Ok(())
 }
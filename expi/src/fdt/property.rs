@@ -72,12 +72,23 @@ impl<T: AsRef<[u8]>> RegEntries<'_, T> {
         let size_idx = address_idx + self.reg.address_cells * 4;
         let end_idx = size_idx + self.reg.size_cells * 4;
 
+        // Entries without cells would never advance the iterator.
+        if end_idx == address_idx {
+            return Err(Error::ConversionError);
+        }
+
         let address_bytes =
             bytes.get(address_idx..size_idx).ok_or(Error::OutOfBounds)?;
         let address = usize_from_be_bytes(address_bytes)?;
-        let size_bytes =
-            bytes.get(size_idx..end_idx).ok_or(Error::OutOfBounds)?;
-        let size = usize_from_be_bytes(size_bytes)?;
+        // A `#size-cells` of zero means that the entries do not have a size.
+        // E.g. the `reg` property of the `/cpus` child nodes.
+        let size = if self.reg.size_cells == 0 {
+            0
+        } else {
+            let size_bytes =
+                bytes.get(size_idx..end_idx).ok_or(Error::OutOfBounds)?;
+            usize_from_be_bytes(size_bytes)?
+        };
 
         self.idx = end_idx;
 
@@ -1,17 +1,24 @@
 //! Global resources.
 
-use core::fmt;
+use core::fmt::{self, Write};
 use core::panic::PanicInfo;
 
-use crate::cpu;
+use crate::cpu::{self, exceptions::Daif, topology::Topology};
 use crate::fdt::{self, Fdt};
-use crate::mm;
+use crate::mm::{
+    self, reserved::ReservedMemory, slab::SlabAllocator, stats::AllocCounters,
+    MemoryMap,
+};
 use crate::print::UartWriter;
+use crate::println;
+#[cfg(feature = "lock-debug")]
+use crate::system_timer;
 use crate::uart;
-use crate::{print, println};
 
-use mutex::TicketMutex;
-use range::RangeSet;
+#[cfg(feature = "lock-debug")]
+use mutex::debug::{self, DebugHooks};
+use mutex::{IrqSafeMcsMutex, IrqSafeMutex, Once};
+use range::TreeRangeSet;
 
 /// Globals error.
 #[derive(Debug)]
@@ -24,6 +31,9 @@ pub enum Error {
 
     /// FDT error.
     FdtError(fdt::Error),
+
+    /// CPU error.
+    CpuError(cpu::Error),
 }
 
 impl From<uart::Error> for Error {
@@ -44,27 +54,76 @@ impl From<fdt::Error> for Error {
     }
 }
 
+impl From<cpu::Error> for Error {
+    fn from(err: cpu::Error) -> Error {
+        Error::CpuError(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::UartError(err) => write!(f, "UART error: {err}"),
             Error::MmError(err) => write!(f, "memory management error: {err}"),
             Error::FdtError(err) => write!(f, "FDT error: {err}"),
+            Error::CpuError(err) => write!(f, "CPU error: {err}"),
         }
     }
 }
 
+/// Mutex protecting a global resource.
+///
+/// Global resources may be used from interrupt handlers (e.g. [`println!`]
+/// or memory allocations). So, IRQs and FIQs are masked while the lock is
+/// held. Otherwise, an interrupt arriving on the core that holds the lock
+/// would deadlock.
+pub type GlobalMutex<T> = IrqSafeMutex<T, Daif>;
+
+/// MCS mutex protecting a global resource. Like [`GlobalMutex`], it masks
+/// IRQs and FIQs while the lock is held.
+pub type GlobalMcsMutex<T> = IrqSafeMcsMutex<T, Daif>;
+
 /// Contains the global resources shared between modules.
 pub struct GlobalResources {
-    /// [`RangeSet`] with the free memory regions.
-    free_memory: TicketMutex<Option<RangeSet>>,
+    /// [`TreeRangeSet`] with the free memory regions.
+    ///
+    /// It is taken by large allocations and whenever the slab allocator
+    /// needs new pages, so it is protected by a [`GlobalMcsMutex`] to keep
+    /// the lock traffic low when several cores allocate memory.
+    free_memory: GlobalMcsMutex<Option<TreeRangeSet>>,
+
+    /// Allocator for small objects. Its caches take slabs from
+    /// `free_memory`, so they must never be locked while holding the
+    /// `free_memory` lock.
+    slab_allocator: SlabAllocator,
+
+    /// Statistics of the global allocator.
+    alloc_counters: AllocCounters,
+
+    /// Physical memory map. It is built once by [`mm::init`] and it is
+    /// read-only afterwards.
+    memory_map: Once<MemoryMap>,
+
+    /// Regions of the `/reserved-memory` node of the devicetree. Like the
+    /// memory map, they are found by [`mm::init`].
+    reserved_memory: Once<ReservedMemory>,
 
-    /// [`UartWriter`] used by the [`print!`] and [`println!`] macros to
-    /// provide safe concurrent access to the UART.
-    uart_writer: TicketMutex<Option<UartWriter>>,
+    /// [`UartWriter`] used by the [`print!`](crate::print!) and
+    /// [`println!`] macros to provide safe concurrent access to the UART.
+    /// It is set by [`uart::init`] once the UART is configured.
+    uart_writer: Once<GlobalMcsMutex<UartWriter>>,
 
     /// Parsed FDT.
-    fdt: TicketMutex<Option<Fdt>>,
+    ///
+    /// It is only written once on initialization, so it can be read from
+    /// several cores without taking any lock.
+    fdt: Once<Fdt>,
+
+    /// CPU topology. Like the FDT, it is read-only after initialization.
+    topology: Once<Topology>,
+
+    /// Set once the global resources have been initialized. See [`init`].
+    initialized: Once<()>,
 }
 
 /// Global resources shared between modules.
@@ -74,26 +133,57 @@ impl GlobalResources {
     /// Creates a new [`GlobalResources`] structure.
     const fn new() -> GlobalResources {
         GlobalResources {
-            free_memory: TicketMutex::new(None),
-            uart_writer: TicketMutex::new(None),
-            fdt: TicketMutex::new(None),
+            free_memory: GlobalMcsMutex::new(None),
+            slab_allocator: SlabAllocator::new(),
+            alloc_counters: AllocCounters::new(),
+            memory_map: Once::new(),
+            reserved_memory: Once::new(),
+            uart_writer: Once::new(),
+            fdt: Once::new(),
+            topology: Once::new(),
+            initialized: Once::new(),
         }
     }
 
     /// Returns a reference to the list of free memory regions.
-    pub fn free_memory(&self) -> &TicketMutex<Option<RangeSet>> {
+    pub fn free_memory(&self) -> &GlobalMcsMutex<Option<TreeRangeSet>> {
         &self.free_memory
     }
 
+    /// Returns the allocator for small objects.
+    pub fn slab_allocator(&self) -> &SlabAllocator {
+        &self.slab_allocator
+    }
+
+    /// Returns the statistics of the global allocator.
+    pub fn alloc_counters(&self) -> &AllocCounters {
+        &self.alloc_counters
+    }
+
+    /// Returns the physical memory map.
+    pub fn memory_map(&self) -> &Once<MemoryMap> {
+        &self.memory_map
+    }
+
+    /// Returns the reserved memory regions.
+    pub fn reserved_memory(&self) -> &Once<ReservedMemory> {
+        &self.reserved_memory
+    }
+
     /// Returns a reference to the UART writer.
-    pub fn uart_writer(&self) -> &TicketMutex<Option<UartWriter>> {
+    pub fn uart_writer(&self) -> &Once<GlobalMcsMutex<UartWriter>> {
         &self.uart_writer
     }
 
     /// Returns the parsed FDT.
-    pub fn fdt(&self) -> &TicketMutex<Option<Fdt>> {
+    pub fn fdt(&self) -> &Once<Fdt> {
         &self.fdt
     }
+
+    /// Returns the CPU topology.
+    pub fn topology(&self) -> &Once<Topology> {
+        &self.topology
+    }
 }
 
 /// Global Allocator.
@@ -101,15 +191,27 @@ impl GlobalResources {
 static GLOBAL_ALLOCATOR: mm::GlobalAllocator = mm::GlobalAllocator;
 
 /// Panic handler.
+///
+/// It writes to the UART without taking the UART writer lock, because the
+/// panic may have been raised while holding it. E.g. when the lock debugging
+/// detects that it is being locked recursively. So, the output of several
+/// cores panicking at the same time may be interleaved.
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    print!("\n\n!!! PANIC !!!\n\n");
+    if uart::is_initialized() {
+        let mut uart_writer = UartWriter;
 
-    if let Some(location) = info.location() {
-        print!("{}:{}", location.file(), location.line());
-    }
+        // The returned `Result`s can be safely ignored because
+        // `UartWriter::write_str` cannot fail.
+        let _ = write!(uart_writer, "\n\n!!! PANIC !!!\n\n");
+
+        if let Some(location) = info.location() {
+            let _ =
+                write!(uart_writer, "{}:{}", location.file(), location.line());
+        }
 
-    println!(": {}", info.message());
+        let _ = writeln!(uart_writer, ": {}", info.message());
+    }
 
     loop {
         cpu::wfe()
@@ -118,11 +220,63 @@ fn panic(info: &PanicInfo) -> ! {
 
 /// Initializes global resources. E.g. UART, global allocator.
 ///
+/// The global resources are initialized exactly once. If several cores call
+/// this function at the same time, one of them performs the initialization
+/// while the others wait for it to finish. If the initialization fails, it
+/// can be retried.
+///
 /// It is required to configure the MMU before calling this function.
 /// Otherwise, atomics won't work.
 pub fn init(dtb_ptr32: u32) -> Result<(), Error> {
-    uart::init()?;
-    mm::init(dtb_ptr32)?;
-    fdt::init(dtb_ptr32)?;
+    GLOBALS.initialized.try_call_once(|| {
+        #[cfg(feature = "lock-debug")]
+        init_lock_debug();
+
+        uart::init()?;
+
+        // SMP coherency is enabled before the UART is initialized, so its
+        // failures are reported here. It is only required to release the
+        // secondary cores.
+        if let Err(err) = cpu::mp::check_smp_coherency() {
+            println!("warning: SMP coherency is not enabled: {err}");
+        }
+
+        mm::init(dtb_ptr32)?;
+        mm::print_memory_map()?;
+        fdt::init(dtb_ptr32)?;
+        cpu::topology::init()?;
+        Ok::<(), Error>(())
+    })?;
     Ok(())
 }
+
+/// Hooks used to collect lock debug information. The core is identified by
+/// the affinity fields of its MPIDR_EL1 register, which can be read without
+/// taking any lock, and hold times are measured in System Timer ticks
+/// (microseconds).
+#[cfg(feature = "lock-debug")]
+static LOCK_DEBUG_HOOKS: DebugHooks = DebugHooks {
+    core_id: || cpu::mp::affinity() as usize,
+    now: system_timer::counter,
+};
+
+/// Sets the lock debug hooks and registers the global locks.
+#[cfg(feature = "lock-debug")]
+fn init_lock_debug() {
+    debug::set_hooks(&LOCK_DEBUG_HOOKS);
+
+    GLOBALS.free_memory.register("free_memory");
+    GLOBALS.slab_allocator.register();
+}
+
+/// Prints the owner and the contention statistics of the registered locks.
+///
+/// It takes the UART writer lock, so it cannot be used to inspect a deadlock
+/// involving that lock. In that case, [`mutex::debug::locks`] can be used to
+/// get the debug information without taking any lock.
+#[cfg(feature = "lock-debug")]
+pub fn dump_locks() {
+    for info in debug::locks() {
+        println!("{info}");
+    }
+}
@@ -41,6 +41,7 @@
 //! [flatelf]: https://github.com/jroimartin/flatelf/
 
 #![no_std]
+#![cfg_attr(feature = "arena", feature(allocator_api))]
 
 extern crate alloc;
 
@@ -59,3 +60,7 @@ pub mod print;
 pub mod ptr;
 pub mod system_timer;
 pub mod uart;
+
+// Re-exported so the exported macros and the code generated by expi_macros can
+// refer to it.
+pub use mutex;
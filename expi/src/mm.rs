@@ -3,20 +3,55 @@
 use core::alloc::{GlobalAlloc, Layout};
 use core::fmt;
 
+use crate::cpu::{self, mmu};
 use crate::fdt;
 use crate::fdt::property::Reg;
-use crate::globals::GLOBALS;
-
-use range::{Range, RangeSet};
-
-/// Base address of the kernel.
-const KERNEL_BASE: u64 = 0x80000;
-
-/// Maximum kernel size.
-///
-/// A memory region of 16 MiB starting at [`KERNEL_BASE`] is reserved for the
-/// kernel when the global allocator is initialized.
-const KERNEL_MAX_SIZE: u64 = 16 * 1024 * 1024;
+use crate::globals::{GlobalMutex, GLOBALS};
+use crate::mmio::{MMIO_BASE, MMIO_SIZE};
+use crate::println;
+
+use mutex::McsNode;
+use range::{Range, RangeMap, TreeRangeSet};
+
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod frames;
+#[cfg(feature = "heap-debug")]
+pub mod heap_debug;
+pub mod reserved;
+pub mod slab;
+pub mod stats;
+#[cfg(feature = "alloc-tracking")]
+pub mod tracking;
+
+use reserved::ReservedRegion;
+use slab::SlabAllocator;
+
+/// Base address of the kernel. The firmware jumps to this address on boot.
+/// The stacks used during boot grow downwards from it.
+pub const KERNEL_BASE: u64 = 0x80000;
+
+/// Size in bytes of the region at address 0 where the firmware places the
+/// armstub, which contains the spin-table release addresses of the secondary
+/// cores (0xd8 to 0xf0).
+const ARMSTUB_SIZE: u64 = 0x1000;
+
+/// Address where the boot core stores the top of the per-core stacks before
+/// releasing the secondary cores. See `expi_macros::entrypoint_mp`.
+pub const STACK_TOP_SLOT: u64 = 0x1000;
+
+/// Lowest address of the stacks used during boot. The page below it holds
+/// [`STACK_TOP_SLOT`].
+pub const BOOT_STACK_BASE: u64 = STACK_TOP_SLOT + frames::PAGE_SIZE as u64;
+
+extern "C" {
+    /// First byte of the kernel image. It is defined by the linker.
+    static __executable_start: u8;
+
+    /// First byte after the kernel image, including `.bss`. It is defined by
+    /// the linker.
+    static _end: u8;
+}
 
 /// Memory management error.
 #[derive(Debug)]
@@ -39,8 +74,36 @@ pub enum Error {
     /// The provided size cannot be zero.
     ZeroSize,
 
-    /// An arithmetic operation caused an integer overflow.
-    IntegerOverflow,
+    /// The order of a block of pages is greater than
+    /// [`frames::MAX_ORDER`].
+    InvalidOrder(u32),
+
+    /// A block of pages is not aligned to the page size.
+    UnalignedPages(Range),
+
+    /// Freed memory that is not a live allocation. E.g. double free or wrong
+    /// layout. It contains the freed address.
+    InvalidFree(usize),
+
+    /// A red zone of an allocation has been overwritten. It contains the
+    /// address of the first corrupted byte.
+    CorruptedRedZone(usize),
+
+    /// The devicetree describes more reserved memory regions than
+    /// [`reserved::MAX_RESERVED_REGIONS`].
+    TooManyReservedRegions,
+
+    /// The name of a reserved memory region is longer than the space
+    /// reserved for it in [`reserved::ReservedRegion`].
+    ReservedNameTooLong,
+
+    /// A `no-map` reserved region shares a 2MB block of the identity mapping
+    /// with memory that must stay mapped. It contains the range of the
+    /// region that could not be reserved.
+    NoMapConflict(Range),
+
+    /// CPU error.
+    CpuError(cpu::Error),
 
     /// FDT error.
     FdtError(fdt::Error),
@@ -49,6 +112,12 @@ pub enum Error {
     RangeError(range::Error),
 }
 
+impl From<cpu::Error> for Error {
+    fn from(err: cpu::Error) -> Error {
+        Error::CpuError(err)
+    }
+}
+
 impl From<fdt::Error> for Error {
     fn from(err: fdt::Error) -> Error {
         Error::FdtError(err)
@@ -76,7 +145,34 @@ impl fmt::Display for Error {
             }
             Error::NullPtr => write!(f, "pointer is null"),
             Error::ZeroSize => write!(f, "size is zero"),
-            Error::IntegerOverflow => write!(f, "integer overflow"),
+            Error::InvalidOrder(order) => {
+                write!(f, "invalid block order: {order}")
+            }
+            Error::UnalignedPages(pages) => write!(
+                f,
+                "unaligned pages: [{:#x}, {:#x}]",
+                pages.start(),
+                pages.end()
+            ),
+            Error::InvalidFree(addr) => {
+                write!(f, "free of unallocated memory at {addr:#x}")
+            }
+            Error::CorruptedRedZone(addr) => {
+                write!(f, "corrupted red zone at {addr:#x}")
+            }
+            Error::TooManyReservedRegions => {
+                write!(f, "too many reserved memory regions")
+            }
+            Error::ReservedNameTooLong => {
+                write!(f, "reserved memory region name is too long")
+            }
+            Error::NoMapConflict(range) => write!(
+                f,
+                "no-map conflict: [{:#x}, {:#x}]",
+                range.start(),
+                range.end()
+            ),
+            Error::CpuError(err) => write!(f, "CPU error: {err}"),
             Error::FdtError(err) => {
                 write!(f, "FDT parsing error: {err}")
             }
@@ -85,6 +181,80 @@ impl fmt::Display for Error {
     }
 }
 
+/// Kind of a physical memory region.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryKind {
+    /// ARM memory that can be used by the global allocator.
+    Ram,
+
+    /// Memory used by the VideoCore.
+    VideoCore,
+
+    /// Memory mapped peripherals.
+    Mmio,
+
+    /// Devicetree blob.
+    Dtb,
+
+    /// Kernel image, including its global variables.
+    Kernel,
+
+    /// Armstub and spin table placed by the firmware.
+    Armstub,
+
+    /// Slots written by the boot code. See [`STACK_TOP_SLOT`].
+    BootSlots,
+
+    /// Stacks used during boot.
+    BootStack,
+
+    /// Region listed in the DTB's memory reservation block or in the
+    /// `/reserved-memory` node.
+    Reserved,
+
+    /// Region of the `/reserved-memory` node that must not be mapped.
+    NoMap,
+}
+
+impl fmt::Display for MemoryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryKind::Ram => write!(f, "RAM"),
+            MemoryKind::VideoCore => write!(f, "VideoCore"),
+            MemoryKind::Mmio => write!(f, "MMIO"),
+            MemoryKind::Dtb => write!(f, "DTB"),
+            MemoryKind::Kernel => write!(f, "kernel"),
+            MemoryKind::Armstub => write!(f, "armstub"),
+            MemoryKind::BootSlots => write!(f, "boot slots"),
+            MemoryKind::BootStack => write!(f, "boot stack"),
+            MemoryKind::Reserved => write!(f, "reserved"),
+            MemoryKind::NoMap => write!(f, "reserved (no-map)"),
+        }
+    }
+}
+
+/// Physical memory map. It associates every known physical memory region
+/// with its [`MemoryKind`].
+pub type MemoryMap = RangeMap<MemoryKind>;
+
+/// Function called when the global allocator runs out of memory. It receives
+/// the layout of the failed allocation and returns true if it released some
+/// memory, in which case the allocation is retried once.
+pub type OomHook = fn(Layout) -> bool;
+
+/// Out-of-memory hook. See [`set_oom_hook`].
+static OOM_HOOK: GlobalMutex<Option<OomHook>> = GlobalMutex::new(None);
+
+/// Sets the function called when an allocation cannot be satisfied, before
+/// the allocator returns a null pointer. It can be used, for instance, to
+/// release caches or to print the memory map.
+///
+/// The hook is called without holding any allocator lock. However, it must
+/// not allocate memory, because a failed allocation would call it again.
+pub fn set_oom_hook(hook: OomHook) {
+    *OOM_HOOK.lock() = Some(hook);
+}
+
 /// A simple allocator that implements the trait [`GlobalAlloc`].
 pub struct GlobalAllocator;
 
@@ -99,31 +269,24 @@ impl GlobalAllocator {
             return Err(Error::InvalidAlign);
         }
 
-        let mut free_mem_mg = GLOBALS.free_memory().lock();
-        let free_mem = free_mem_mg.as_mut().ok_or(Error::Uninitialized)?;
-
-        let size = alloc_size(&layout);
-        let align = layout.align() as u64;
-
-        let mut reserved = None;
-        for region in free_mem.ranges() {
-            let start = region
-                .start()
-                .checked_add(align - 1)
-                .ok_or(Error::IntegerOverflow)?
-                & !(align - 1);
-            let end =
-                start.checked_add(size - 1).ok_or(Error::IntegerOverflow)?;
-            if end <= region.end() {
-                reserved = Some(Range::new(start, end)?);
-                break;
-            }
-        }
+        #[cfg(feature = "heap-debug")]
+        return heap_debug::alloc(self, layout);
 
-        let reserved = reserved.ok_or(Error::NotSatisfiable)?;
-        free_mem.remove(reserved)?;
+        #[cfg(not(feature = "heap-debug"))]
+        self.alloc_block(layout)
+    }
 
-        Ok(reserved.start() as *mut u8)
+    /// Allocates a block of memory for `layout`, whose size must not be zero
+    /// and whose alignment must be valid.
+    fn alloc_block(&self, layout: Layout) -> Result<*mut u8, Error> {
+        // Small allocations are served by the slab allocator.
+        if let Some(class) = SlabAllocator::size_class(&layout) {
+            return GLOBALS.slab_allocator().alloc(class);
+        }
+
+        let layout = layout.pad_to_align();
+        let range = alloc_range(layout.size() as u64, layout.align() as u64)?;
+        Ok(range.start() as *mut u8)
     }
 
     /// Tries to deallocate memory.
@@ -140,70 +303,242 @@ impl GlobalAllocator {
             return Err(Error::InvalidAlign);
         }
 
-        let mut free_mem_mg = GLOBALS.free_memory().lock();
-        let free_mem = free_mem_mg.as_mut().ok_or(Error::Uninitialized)?;
+        #[cfg(feature = "heap-debug")]
+        return unsafe { heap_debug::dealloc(self, ptr, layout) };
 
-        let size = alloc_size(&layout);
+        #[cfg(not(feature = "heap-debug"))]
+        unsafe {
+            self.dealloc_block(ptr, layout)
+        }
+    }
 
-        let start = ptr as u64;
-        let end = start.checked_add(size - 1).ok_or(Error::IntegerOverflow)?;
-        let reserved = Range::new(start, end)?;
+    /// Deallocates a block of memory returned by
+    /// [`GlobalAllocator::alloc_block`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`GlobalAllocator::alloc_block`]
+    /// with the same layout and it must not be used afterwards.
+    unsafe fn dealloc_block(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+    ) -> Result<(), Error> {
+        if let Some(class) = SlabAllocator::size_class(&layout) {
+            unsafe { GLOBALS.slab_allocator().dealloc(ptr, class) };
+            return Ok(());
+        }
 
-        free_mem.insert(reserved)?;
+        let size = layout.pad_to_align().size() as u64;
+        free_range(Range::from_start_size(ptr as u64, size)?)
+    }
 
-        Ok(())
+    /// Tries to resize an allocation without moving it. It returns false if
+    /// the allocation must be moved.
+    ///
+    /// Small allocations can only be resized within their size class. Large
+    /// allocations are shrunk by returning their tail to the free memory and
+    /// grown by taking the free memory right after them.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a live allocation with the given layout and `new_size`
+    /// must not be zero.
+    unsafe fn resize_in_place(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<bool, Error> {
+        // The red zones would have to be moved.
+        if cfg!(feature = "heap-debug") {
+            return Ok(false);
+        }
+
+        let Ok(new_layout) = Layout::from_size_align(new_size, layout.align())
+        else {
+            return Ok(false);
+        };
+
+        match (
+            SlabAllocator::size_class(&layout),
+            SlabAllocator::size_class(&new_layout),
+        ) {
+            (None, None) => {}
+            (old_class, new_class) => return Ok(old_class == new_class),
+        }
+
+        let start = ptr as u64;
+        let old_size = layout.pad_to_align().size() as u64;
+        let new_size = new_layout.pad_to_align().size() as u64;
+
+        if new_size < old_size {
+            let tail =
+                Range::from_start_size(start + new_size, old_size - new_size)?;
+            free_range(tail)?;
+        } else if new_size > old_size {
+            let tail =
+                Range::from_start_size(start + old_size, new_size - old_size)?;
+            return take_range(tail);
+        }
+
+        Ok(true)
     }
 }
 
-/// Returns the allocation size for a given layout.
-///
-/// It reduces fragmentation by grouping small allocations into frequent
-/// allocation sizes.
-fn alloc_size(layout: &Layout) -> u64 {
-    let layout = layout.pad_to_align();
-    let size = layout.size();
-
-    if size <= 32 {
-        return 32;
-    } else if size <= 64 {
-        return 64;
-    } else if size <= 128 {
-        return 128;
-    } else if size <= 256 {
-        return 256;
-    } else if size <= 512 {
-        return 512;
+/// Takes the lowest range of `size` bytes aligned to `align` from the free
+/// memory.
+pub(crate) fn alloc_range(size: u64, align: u64) -> Result<Range, Error> {
+    alloc_range_below(size, align, u64::MAX)
+}
+
+/// Takes the lowest range of `size` bytes aligned to `align` from the free
+/// memory. The end point of the range must be lower than `limit`.
+pub(crate) fn alloc_range_below(
+    size: u64,
+    align: u64,
+    limit: u64,
+) -> Result<Range, Error> {
+    let mut node = McsNode::new();
+    let mut free_mem_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
+    let free_mem = free_mem_mg.as_mut().ok_or(Error::Uninitialized)?;
+
+    // The first fit is the lowest one, so if it is not below the limit,
+    // there is no other fit below it.
+    let range = free_mem
+        .first_fit(size, align)
+        .filter(|range| range.end() < limit)
+        .ok_or(Error::NotSatisfiable)?;
+    free_mem.remove(range)?;
+
+    Ok(range)
+}
+
+/// Returns a range to the free memory.
+pub(crate) fn free_range(range: Range) -> Result<(), Error> {
+    let mut node = McsNode::new();
+    let mut free_mem_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
+    let free_mem = free_mem_mg.as_mut().ok_or(Error::Uninitialized)?;
+
+    free_mem.insert(range)?;
+
+    Ok(())
+}
+
+/// Removes `range` from the free memory if all of it is free. It returns
+/// whether the range was removed.
+pub(crate) fn take_range(range: Range) -> Result<bool, Error> {
+    let mut node = McsNode::new();
+    let mut free_mem_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
+    let free_mem = free_mem_mg.as_mut().ok_or(Error::Uninitialized)?;
+
+    if !free_mem.contains_range(range) {
+        return Ok(false);
     }
+    free_mem.remove(range)?;
 
-    size as u64
+    Ok(true)
 }
 
 unsafe impl GlobalAlloc for GlobalAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.try_alloc(layout)
-            .unwrap_or_else(|err| panic!("alloc error: {err:?}"))
+        let mut result = self.try_alloc(layout);
+        if matches!(result, Err(Error::NotSatisfiable)) {
+            // Copy the hook, so the lock is not held while it runs.
+            let hook = *OOM_HOOK.lock();
+            if hook.is_some_and(|hook| hook(layout)) {
+                result = self.try_alloc(layout);
+            }
+        }
+
+        GLOBALS
+            .alloc_counters()
+            .record_alloc(&layout, result.is_ok());
+
+        #[cfg(feature = "alloc-tracking")]
+        if let Ok(ptr) = result {
+            tracking::track(ptr, layout.size());
+        }
+
+        // Running out of memory is reported with a null pointer, so fallible
+        // allocations like `Vec::try_reserve` can handle it. Any other error
+        // means that the allocator state is broken.
+        match result {
+            Ok(ptr) => ptr,
+            Err(Error::NotSatisfiable) => core::ptr::null_mut(),
+            Err(err) => panic!("alloc error: {err}"),
+        }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // The allocation is untracked before releasing it. Otherwise, another
+        // core could get the same address and its entry would be removed.
+        #[cfg(feature = "alloc-tracking")]
+        tracking::untrack(ptr);
+
         self.try_dealloc(ptr, layout)
-            .unwrap_or_else(|err| panic!("dealloc error: {err:?}"))
+            .unwrap_or_else(|err| panic!("dealloc error: {err}"));
+        GLOBALS.alloc_counters().record_dealloc(&layout);
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        let resized = unsafe { self.resize_in_place(ptr, layout, new_size) }
+            .unwrap_or_else(|err| panic!("realloc error: {err}"));
+
+        let new_layout = unsafe {
+            Layout::from_size_align_unchecked(new_size, layout.align())
+        };
+
+        if !resized {
+            let new_ptr = unsafe { self.alloc(new_layout) };
+            if !new_ptr.is_null() {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        ptr,
+                        new_ptr,
+                        layout.size().min(new_size),
+                    );
+                    self.dealloc(ptr, layout);
+                }
+            }
+            return new_ptr;
+        }
+
+        // Like when the allocation is moved, resizing it is recorded as a
+        // deallocation followed by an allocation.
+        GLOBALS.alloc_counters().record_dealloc(&layout);
+        GLOBALS.alloc_counters().record_alloc(&new_layout, true);
+
+        #[cfg(feature = "alloc-tracking")]
+        {
+            tracking::untrack(ptr);
+            tracking::track(ptr, new_size);
+        }
+
+        ptr
     }
 }
 
 /// Initializes the global allocator with the list of free memory regions.
 pub fn init(dtb_ptr32: u32) -> Result<(), Error> {
-    let mut free_mem_mg = GLOBALS.free_memory().lock();
+    let mut node = McsNode::new();
+    let mut free_mem_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
     if free_mem_mg.is_some() {
         // Already initialized.
         return Ok(());
     }
 
-    let mut free_mem = RangeSet::new();
+    let mut memory_map = MemoryMap::new();
 
     // Parse DTB.
     let early_fdt = unsafe { fdt::EarlyFdt::parse(dtb_ptr32 as usize)? };
 
-    // Add ARM memory to the free memory RangeSet.
+    // Add ARM memory to the memory map.
     let root_off = early_fdt.node("/")?;
     let address_cells =
         early_fdt.property(root_off, "#address-cells")?.to_u32()?;
@@ -221,43 +556,111 @@ pub fn init(dtb_ptr32: u32) -> Result<(), Error> {
             for entry in reg.entries() {
                 let (address, size) = entry?;
                 let region =
-                    Range::new(address as u64, (address + size - 1) as u64)?;
-                free_mem.insert(region)?;
+                    Range::from_start_size(address as u64, size as u64)?;
+                memory_map.insert(region, MemoryKind::Ram)?;
             }
         }
     }
 
+    // The VideoCore memory is located between the end of the ARM memory and
+    // the peripherals.
+    let mmio_base = MMIO_BASE as u64;
+    let ram_end = memory_map.ranges().last().map(|r| r.end());
+    if let Some(ram_end) = ram_end.filter(|&end| end + 1 < mmio_base) {
+        let vc_region = Range::try_from(ram_end + 1..mmio_base)?;
+        memory_map.insert(vc_region, MemoryKind::VideoCore)?;
+    }
+
+    let mmio_region = Range::from_start_size(mmio_base, MMIO_SIZE as u64)?;
+    memory_map.insert(mmio_region, MemoryKind::Mmio)?;
+
     // Reserve the memory region where the DTB itself is stored.
     let fdt_size = early_fdt.header().totalsize();
-    let dtb_region =
-        Range::new(dtb_ptr32 as u64, (dtb_ptr32 + fdt_size - 1) as u64)?;
-    free_mem.remove(dtb_region)?;
+    let dtb_region = Range::from_start_size(dtb_ptr32 as u64, fdt_size as u64)?;
+    memory_map.insert(dtb_region, MemoryKind::Dtb)?;
 
     // Reserve the regions found in the DTB's memory reservation block.
     for region in early_fdt.mem_rsv_block_regions() {
         let region = region?;
 
-        let addr = region.address();
-        let size = region.size();
-        let rsv = Range::new(addr, addr + size - 1)?;
-        free_mem.remove(rsv)?;
+        let rsv = Range::from_start_size(region.address(), region.size())?;
+        memory_map.insert(rsv, MemoryKind::Reserved)?;
+    }
+
+    // Reserve the memory used during boot, which is below the kernel.
+    let armstub = Range::try_from(0..ARMSTUB_SIZE)?;
+    memory_map.insert(armstub, MemoryKind::Armstub)?;
+    let boot_slots = Range::try_from(STACK_TOP_SLOT..BOOT_STACK_BASE)?;
+    memory_map.insert(boot_slots, MemoryKind::BootSlots)?;
+    let boot_stack = Range::try_from(BOOT_STACK_BASE..KERNEL_BASE)?;
+    memory_map.insert(boot_stack, MemoryKind::BootStack)?;
+
+    // Reserve the memory region where the kernel is located. Its end is
+    // rounded up to a page boundary, so the free memory stays page aligned.
+    let kernel_start = &raw const __executable_start as u64;
+    let kernel_end = (&raw const _end as u64)
+        .checked_next_multiple_of(frames::PAGE_SIZE as u64)
+        .ok_or(Error::NotSatisfiable)?;
+    let kernel_region = Range::try_from(kernel_start..kernel_end)?;
+    memory_map.insert(kernel_region, MemoryKind::Kernel)?;
+
+    // Reserve the regions of the `/reserved-memory` node. The dynamic ones
+    // are allocated from the RAM that has not been reserved yet.
+    let reserved_memory = reserved::parse(&early_fdt, &mut memory_map)?;
+    for blocks in reserved_memory.iter().filter_map(|r| r.unmapped_range()) {
+        unsafe { mmu::unmap_identity(blocks)? };
     }
 
-    // Reserve the memory region where the kernel is located. It starts at 0,
-    // because the range [0, KERNEL_BASE) is used for global variables (first
-    // two pages) and for the stack used during initialization.
-    let kernel_region = Range::new(0, KERNEL_BASE + KERNEL_MAX_SIZE - 1)?;
-    free_mem.remove(kernel_region)?;
+    // The free memory is the ARM memory that has not been reserved.
+    let mut free_mem = TreeRangeSet::new();
+    for (region, &kind) in &memory_map {
+        if kind == MemoryKind::Ram {
+            free_mem.insert(region)?;
+        }
+    }
 
     // Set globals.
+    GLOBALS.memory_map().call_once(|| memory_map);
+    GLOBALS.reserved_memory().call_once(|| reserved_memory);
     *free_mem_mg = Some(free_mem);
 
     Ok(())
 }
 
+/// Prints the physical memory map.
+pub fn print_memory_map() -> Result<(), Error> {
+    let memory_map = GLOBALS.memory_map().get().ok_or(Error::Uninitialized)?;
+
+    println!("physical memory map:");
+    for (region, kind) in memory_map {
+        println!("  {:#010x}-{:#010x} {kind}", region.start(), region.end());
+    }
+
+    if let Some(reserved_memory) = GLOBALS.reserved_memory().get() {
+        for region in reserved_memory.iter() {
+            let range = region.range();
+            println!(
+                "  {:#010x}-{:#010x} reserved by {}",
+                range.start(),
+                range.end(),
+                region.name()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the reserved memory region described by the child of the
+/// `/reserved-memory` node called `name`.
+pub fn reserved_region(name: &str) -> Option<&'static ReservedRegion> {
+    GLOBALS.reserved_memory().get()?.get(name)
+}
+
 /// Returns the size in bytes of the memory that is currently free.
 pub fn free_memory_size() -> Result<u64, Error> {
-    let free_mem_mg = GLOBALS.free_memory().lock();
+    let mut node = McsNode::new();
+    let free_mem_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
     let free_mem = free_mem_mg.as_ref().ok_or(Error::Uninitialized)?;
     Ok(free_mem.size())
 }
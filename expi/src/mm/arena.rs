@@ -0,0 +1,113 @@
+//! Arena allocator.
+//!
+//! An [`Arena`] takes a chunk of memory from the global heap and hands it out
+//! by bumping an offset, so allocating is cheap and there is no per-object
+//! bookkeeping. Individual deallocations are ignored, except for the most
+//! recent allocation, and the whole chunk is reclaimed at once with
+//! [`Arena::reset`] or when the arena is dropped.
+//!
+//! It implements the [`Allocator`] trait, which requires a nightly toolchain
+//! and the `arena` feature. Collections can use it through a reference:
+//!
+//! ```text
+//! let arena = Arena::new(4096)?;
+//! let mut v = Vec::new_in(&arena);
+//! v.push(1);
+//! ```
+
+use alloc::alloc::{alloc, dealloc};
+use core::alloc::{AllocError, Allocator, Layout};
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use crate::mm::Error;
+
+/// Alignment of the chunk of an arena. Allocations with a bigger alignment
+/// are padded within the chunk.
+const CHUNK_ALIGN: usize = 16;
+
+/// Bump allocator backed by a chunk of the global heap.
+///
+/// It is not [`Sync`], so every core must use its own arenas.
+#[derive(Debug)]
+pub struct Arena {
+    /// Chunk taken from the global heap.
+    chunk: NonNull<u8>,
+
+    /// Layout of `chunk`.
+    layout: Layout,
+
+    /// Offset of the first free byte of `chunk`.
+    offset: Cell<usize>,
+}
+
+impl Arena {
+    /// Returns an [`Arena`] that can hold `capacity` bytes.
+    pub fn new(capacity: usize) -> Result<Arena, Error> {
+        if capacity == 0 {
+            return Err(Error::ZeroSize);
+        }
+
+        let layout = Layout::from_size_align(capacity, CHUNK_ALIGN)
+            .map_err(|_| Error::NotSatisfiable)?;
+        let chunk = unsafe { alloc(layout) };
+        let chunk = NonNull::new(chunk).ok_or(Error::NotSatisfiable)?;
+
+        Ok(Arena {
+            chunk,
+            layout,
+            offset: Cell::new(0),
+        })
+    }
+
+    /// Returns the size in bytes of the chunk.
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns the number of bytes handed out, including padding.
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// Frees every allocation at once.
+    ///
+    /// It takes a mutable reference, so the collections that borrow the arena
+    /// must have been dropped.
+    pub fn reset(&mut self) {
+        self.offset.set(0);
+    }
+}
+
+unsafe impl Allocator for Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let base = self.chunk.as_ptr() as usize;
+        let start = (base + self.offset.get())
+            .checked_next_multiple_of(layout.align())
+            .ok_or(AllocError)?;
+        let end = start.checked_add(layout.size()).ok_or(AllocError)?;
+        if end > base + self.capacity() {
+            return Err(AllocError);
+        }
+
+        self.offset.set(end - base);
+
+        let ptr = unsafe { self.chunk.add(start - base) };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // Only the most recent allocation can be given back.
+        let base = self.chunk.as_ptr() as usize;
+        let start = ptr.as_ptr() as usize;
+        if start + layout.size() == base + self.offset.get() {
+            self.offset.set(start - base);
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.chunk.as_ptr(), self.layout) };
+    }
+}
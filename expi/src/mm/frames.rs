@@ -0,0 +1,78 @@
+//! Physical page frame allocator.
+//!
+//! It hands out physically contiguous, page-aligned blocks of memory taken
+//! from the free memory discovered by [`mm::init`]. Like in a buddy
+//! allocator, a block of order `n` is made of `2^n` pages and it is aligned
+//! to its size. The free memory is kept in a [`TreeRangeSet`], which merges
+//! the freed blocks with their neighbors, so there is no need to keep free
+//! lists per order.
+//!
+//! The memory is identity mapped, so the physical address of a block can be
+//! used as a pointer.
+//!
+//! [`TreeRangeSet`]: range::TreeRangeSet
+
+use crate::mm::{self, Error};
+
+use range::Range;
+
+/// Size in bytes of a page.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Maximum order of a block. A block of this order spans 1 GiB.
+pub const MAX_ORDER: u32 = 18;
+
+/// Lowest address that cannot be reached by 32-bit DMA masters.
+const LIMIT_4G: u64 = 1 << 32;
+
+/// Allocates a block of `2^order` contiguous pages aligned to its size.
+pub fn alloc_pages(order: u32) -> Result<Range, Error> {
+    if order > MAX_ORDER {
+        return Err(Error::InvalidOrder(order));
+    }
+
+    let size = (PAGE_SIZE as u64) << order;
+    mm::alloc_range(size, size)
+}
+
+/// Allocates a block of contiguous pages that can hold `size` bytes and
+/// whose address is aligned to `align`, which must be a power of two. The
+/// block is always aligned to [`PAGE_SIZE`]. If `below_4g` is true, the whole
+/// block is located below 4 GiB.
+pub fn alloc_contiguous(
+    size: usize,
+    align: usize,
+    below_4g: bool,
+) -> Result<Range, Error> {
+    if size == 0 {
+        return Err(Error::ZeroSize);
+    }
+
+    if !align.is_power_of_two() {
+        return Err(Error::InvalidAlign);
+    }
+
+    let size = size
+        .checked_next_multiple_of(PAGE_SIZE)
+        .ok_or(Error::NotSatisfiable)?;
+    let align = align.max(PAGE_SIZE);
+    let limit = if below_4g { LIMIT_4G } else { u64::MAX };
+
+    mm::alloc_range_below(size as u64, align as u64, limit)
+}
+
+/// Frees a block of pages returned by [`alloc_pages`] or
+/// [`alloc_contiguous`].
+///
+/// # Safety
+///
+/// `pages` must have been returned by one of the allocation functions of
+/// this module and it must not be used afterwards.
+pub unsafe fn free_pages(pages: Range) -> Result<(), Error> {
+    let page_mask = PAGE_SIZE as u64 - 1;
+    if pages.start() & page_mask != 0 || pages.size() & page_mask != 0 {
+        return Err(Error::UnalignedPages(pages));
+    }
+
+    mm::free_range(pages)
+}
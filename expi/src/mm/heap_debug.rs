@@ -0,0 +1,142 @@
+//! Heap corruption detection.
+//!
+//! When the `heap-debug` feature is enabled, the global allocator surrounds
+//! every allocation with red zones filled with a canary pattern and checks
+//! them when the allocation is freed. Newly allocated memory is filled with
+//! [`ALLOC_POISON`] and freed memory with [`FREE_POISON`], so uses of
+//! uninitialized or freed memory are easier to spot.
+//!
+//! The live allocations are kept in a [`TreeRangeSet`]. The red zones
+//! separate the allocations, so every range of the set is exactly one
+//! allocation. Thus, double frees and frees of memory that was never
+//! allocated are detected before the memory is returned to the allocator.
+
+use core::alloc::Layout;
+use core::slice;
+
+use crate::globals::GlobalMutex;
+use crate::mm::{Error, GlobalAllocator};
+
+use range::{Range, TreeRangeSet};
+
+/// Maximum number of live allocations.
+const MAX_LIVE: usize = 2048;
+
+/// Size in bytes of the red zone placed after every allocation. The red zone
+/// placed before an allocation is as big as its alignment, with this size as
+/// minimum.
+const RED_ZONE_SIZE: usize = 16;
+
+/// Byte pattern written in the red zones.
+const CANARY: u8 = 0xfd;
+
+/// Byte pattern written in newly allocated memory.
+pub const ALLOC_POISON: u8 = 0xcd;
+
+/// Byte pattern written in freed memory.
+pub const FREE_POISON: u8 = 0xdd;
+
+/// Live allocations, without their red zones.
+///
+/// The set is created by the first allocation. Otherwise, its initial value
+/// would have to be stored in the kernel image.
+static LIVE: GlobalMutex<Option<TreeRangeSet<MAX_LIVE>>> =
+    GlobalMutex::new(None);
+
+/// Returns the layout of an allocation including its red zones and the size
+/// of the red zone placed before the allocation.
+fn outer_layout(layout: &Layout) -> Result<(Layout, usize), Error> {
+    let front = layout.align().max(RED_ZONE_SIZE);
+    let size = front
+        .checked_add(layout.size())
+        .and_then(|size| size.checked_add(RED_ZONE_SIZE))
+        .ok_or(Error::NotSatisfiable)?;
+    let outer = Layout::from_size_align(size, layout.align())
+        .map_err(|_| Error::NotSatisfiable)?;
+    Ok((outer, front))
+}
+
+/// Checks that every byte of `zone` matches [`CANARY`]. Otherwise, the error
+/// contains the address of the first corrupted byte.
+fn check_red_zone(zone: &[u8]) -> Result<(), Error> {
+    match zone.iter().position(|&b| b != CANARY) {
+        Some(i) => Err(Error::CorruptedRedZone(zone.as_ptr() as usize + i)),
+        None => Ok(()),
+    }
+}
+
+/// Allocates memory for `layout` surrounded by red zones.
+pub(crate) fn alloc(
+    allocator: &GlobalAllocator,
+    layout: Layout,
+) -> Result<*mut u8, Error> {
+    let (outer, front) = outer_layout(&layout)?;
+    let base = allocator.alloc_block(outer)?;
+    let ptr = unsafe { base.add(front) };
+
+    let range = Range::from_start_size(ptr as u64, layout.size() as u64)?;
+    let inserted = LIVE
+        .lock()
+        .get_or_insert_with(TreeRangeSet::new)
+        .insert(range);
+    if let Err(err) = inserted {
+        unsafe { allocator.dealloc_block(base, outer)? };
+        return Err(err.into());
+    }
+
+    unsafe {
+        base.write_bytes(CANARY, front);
+        ptr.write_bytes(ALLOC_POISON, layout.size());
+        ptr.add(layout.size()).write_bytes(CANARY, RED_ZONE_SIZE);
+    }
+
+    Ok(ptr)
+}
+
+/// Checks and deallocates memory returned by [`alloc`].
+///
+/// # Safety
+///
+/// If `ptr` is a live allocation, it must not be used afterwards.
+pub(crate) unsafe fn dealloc(
+    allocator: &GlobalAllocator,
+    ptr: *mut u8,
+    layout: Layout,
+) -> Result<(), Error> {
+    let range = Range::from_start_size(ptr as u64, layout.size() as u64)?;
+
+    {
+        let mut live_mg = LIVE.lock();
+        let live = live_mg.as_mut().ok_or(Error::InvalidFree(ptr as usize))?;
+
+        // The range must be a whole allocation, not only part of one.
+        let is_live = live.contains_range(range)
+            && !range
+                .start()
+                .checked_sub(1)
+                .is_some_and(|prev| live.contains_point(prev))
+            && !range
+                .end()
+                .checked_add(1)
+                .is_some_and(|next| live.contains_point(next));
+        if !is_live {
+            return Err(Error::InvalidFree(ptr as usize));
+        }
+
+        live.remove(range)?;
+    }
+
+    // The allocation is not live anymore, so nobody else can access it.
+    let (outer, front) = outer_layout(&layout)?;
+    let base = unsafe { ptr.sub(front) };
+    let head = unsafe { slice::from_raw_parts(base, front) };
+    let tail =
+        unsafe { slice::from_raw_parts(ptr.add(layout.size()), RED_ZONE_SIZE) };
+    check_red_zone(head)?;
+    check_red_zone(tail)?;
+
+    unsafe {
+        base.write_bytes(FREE_POISON, outer.size());
+        allocator.dealloc_block(base, outer)
+    }
+}
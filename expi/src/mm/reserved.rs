@@ -0,0 +1,372 @@
+//! Reserved memory regions.
+//!
+//! The children of the `/reserved-memory` node of the devicetree describe
+//! memory regions that must not be used by the global allocator. Static
+//! regions are given by a `reg` property. Dynamic regions are given by a
+//! `size` property and, optionally, by the `alignment` and `alloc-ranges`
+//! properties. They are allocated from the RAM that is still available once
+//! the static regions have been reserved, taking the lowest suitable address.
+//!
+//! The regions with the `no-map` property are removed from the identity
+//! mapping. It is built with 2MB blocks, so the whole blocks that contain
+//! them are reserved and unmapped. Those blocks cannot hold any other memory
+//! in use, like the kernel or other reserved regions.
+//!
+//! Regions marked as `reusable` are reserved like any other region, because
+//! the global allocator cannot give memory back on demand.
+//!
+//! Drivers find their regions by node name with [`mm::reserved_region`].
+//!
+//! [`mm::reserved_region`]: crate::mm::reserved_region
+
+use core::cmp::{max, min};
+use core::str;
+
+use crate::cpu::mmu::L2_BLOCK_SIZE;
+use crate::fdt::{self, property::Reg, EarlyFdt, FdtPtr, RefProperty};
+use crate::mm::frames::PAGE_SIZE;
+use crate::mm::{Error, MemoryKind, MemoryMap};
+
+use range::Range;
+
+/// Maximum number of reserved memory regions.
+pub const MAX_RESERVED_REGIONS: usize = 16;
+
+/// Maximum length in bytes of the name of a reserved memory region.
+const MAX_NAME_LEN: usize = 64;
+
+/// Reserved memory region.
+#[derive(Debug, Copy, Clone)]
+pub struct ReservedRegion {
+    /// Name of the node, including its unit address.
+    name: [u8; MAX_NAME_LEN],
+
+    /// Length of the name.
+    name_len: usize,
+
+    /// Physical memory range.
+    range: Range,
+
+    /// Whole 2MB blocks removed from the identity mapping, if the region is
+    /// not mapped.
+    unmapped_range: Option<Range>,
+
+    /// True if the region is marked as reusable in the devicetree.
+    reusable: bool,
+}
+
+impl ReservedRegion {
+    /// Returns the name of the node that describes the region.
+    pub fn name(&self) -> &str {
+        str::from_utf8(&self.name[..self.name_len]).unwrap_or_default()
+    }
+
+    /// Returns the physical memory range of the region.
+    pub fn range(&self) -> Range {
+        self.range
+    }
+
+    /// Returns true if the region has been removed from the identity
+    /// mapping.
+    pub fn no_map(&self) -> bool {
+        self.unmapped_range.is_some()
+    }
+
+    /// Returns the range removed from the identity mapping for a `no-map`
+    /// region. It is made of the whole 2MB blocks that contain the region,
+    /// which are reserved too.
+    pub fn unmapped_range(&self) -> Option<Range> {
+        self.unmapped_range
+    }
+
+    /// Returns true if the region is marked as reusable in the devicetree.
+    pub fn reusable(&self) -> bool {
+        self.reusable
+    }
+}
+
+/// Reserved memory regions found in the devicetree.
+#[derive(Debug)]
+pub struct ReservedMemory {
+    /// Reserved regions. A static region with several `reg` entries takes
+    /// one element per entry.
+    regions: [Option<ReservedRegion>; MAX_RESERVED_REGIONS],
+}
+
+impl ReservedMemory {
+    /// Returns an empty [`ReservedMemory`].
+    const fn new() -> ReservedMemory {
+        ReservedMemory {
+            regions: [None; MAX_RESERVED_REGIONS],
+        }
+    }
+
+    /// Appends a region.
+    fn push(&mut self, region: ReservedRegion) -> Result<(), Error> {
+        let slot = self
+            .regions
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(Error::TooManyReservedRegions)?;
+        *slot = Some(region);
+        Ok(())
+    }
+
+    /// Returns an iterator over the reserved regions.
+    pub fn iter(&self) -> impl Iterator<Item = &ReservedRegion> {
+        self.regions.iter().map_while(Option::as_ref)
+    }
+
+    /// Returns the first region described by the node called `name`.
+    pub fn get(&self, name: &str) -> Option<&ReservedRegion> {
+        self.iter().find(|region| region.name() == name)
+    }
+}
+
+/// Properties of a child of the `/reserved-memory` node.
+struct Child<'a> {
+    /// Name of the node.
+    name: &'a str,
+
+    /// Pointer to the node.
+    ptr: FdtPtr,
+
+    /// True if the node has the `no-map` property.
+    no_map: bool,
+
+    /// True if the node has the `reusable` property.
+    reusable: bool,
+}
+
+impl Child<'_> {
+    /// Reserves `range` in the memory map and returns the corresponding
+    /// [`ReservedRegion`].
+    ///
+    /// For `no-map` regions, the whole 2MB blocks that contain `range` are
+    /// reserved. They can only overlap memory that is not in use. Likewise,
+    /// other regions cannot overlap the blocks of a `no-map` region.
+    fn reserve(
+        &self,
+        memory_map: &mut MemoryMap,
+        range: Range,
+    ) -> Result<ReservedRegion, Error> {
+        let len = self.name.len();
+        if len > MAX_NAME_LEN {
+            return Err(Error::ReservedNameTooLong);
+        }
+
+        let mut name = [0; MAX_NAME_LEN];
+        name[..len].copy_from_slice(self.name.as_bytes());
+
+        let unmapped_range = if self.no_map {
+            let blocks = block_range(range)?;
+            let in_use = memory_map.iter().any(|(region, &kind)| {
+                region.overlaps(blocks)
+                    && !matches!(
+                        kind,
+                        MemoryKind::Ram
+                            | MemoryKind::VideoCore
+                            | MemoryKind::NoMap
+                    )
+            });
+            if in_use {
+                return Err(Error::NoMapConflict(range));
+            }
+            memory_map.insert(blocks, MemoryKind::NoMap)?;
+            Some(blocks)
+        } else {
+            let unmapped = memory_map.iter().any(|(region, &kind)| {
+                region.overlaps(range) && kind == MemoryKind::NoMap
+            });
+            if unmapped {
+                return Err(Error::NoMapConflict(range));
+            }
+            memory_map.insert(range, MemoryKind::Reserved)?;
+            None
+        };
+
+        Ok(ReservedRegion {
+            name,
+            name_len: len,
+            range,
+            unmapped_range,
+            reusable: self.reusable,
+        })
+    }
+}
+
+/// Returns the range made of the whole 2MB blocks that contain `range`.
+fn block_range(range: Range) -> Result<Range, Error> {
+    let mask = L2_BLOCK_SIZE - 1;
+    Ok(Range::new(range.start() & !mask, range.end() | mask)?)
+}
+
+/// Returns the property called `name` of the node pointed by `node_ptr`, or
+/// `None` if the node does not have it. Other errors, like a malformed FDT,
+/// are returned.
+fn optional_property<'a>(
+    early_fdt: &'a EarlyFdt,
+    node_ptr: FdtPtr,
+    name: &str,
+) -> Result<Option<RefProperty<'a>>, fdt::Error> {
+    match early_fdt.property(node_ptr, name) {
+        Ok(property) => Ok(Some(property)),
+        Err(fdt::Error::NotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns the value of a property made of one or two cells.
+fn cells_value(property: RefProperty) -> Result<u64, Error> {
+    match property.as_ref().len() {
+        4 => Ok(property.to_u32()?.into()),
+        _ => Ok(property.to_u64()?),
+    }
+}
+
+/// Returns the lowest range of `size` bytes aligned to `align` that is in
+/// RAM and within `bounds`.
+fn first_fit(
+    memory_map: &MemoryMap,
+    size: u64,
+    align: u64,
+    bounds: Range,
+) -> Option<Range> {
+    memory_map
+        .iter()
+        .filter(|&(_, &kind)| kind == MemoryKind::Ram)
+        .find_map(|(region, _)| {
+            let start = max(region.start(), bounds.start())
+                .checked_next_multiple_of(align)?;
+            let end = min(region.end(), bounds.end());
+            let range = Range::from_start_size(start, size).ok()?;
+            (range.end() <= end).then_some(range)
+        })
+}
+
+/// Parses the `/reserved-memory` node of the devicetree and reserves its
+/// regions in the memory map. Static regions are reserved before the
+/// dynamic ones are allocated, so they never overlap.
+pub(crate) fn parse(
+    early_fdt: &EarlyFdt,
+    memory_map: &mut MemoryMap,
+) -> Result<ReservedMemory, Error> {
+    let mut reserved_memory = ReservedMemory::new();
+
+    let node_ptr = match early_fdt.node("/reserved-memory") {
+        Ok(node_ptr) => node_ptr,
+        Err(fdt::Error::NotFound) => return Ok(reserved_memory),
+        Err(err) => return Err(err.into()),
+    };
+
+    let address_cells =
+        early_fdt.property(node_ptr, "#address-cells")?.to_u32()?;
+    let size_cells = early_fdt.property(node_ptr, "#size-cells")?.to_u32()?;
+
+    let children = || {
+        early_fdt.children(node_ptr).filter_map(|child| {
+            child
+                .and_then(|(name, ptr)| {
+                    let property =
+                        |name| optional_property(early_fdt, ptr, name);
+
+                    // Disabled nodes must be ignored.
+                    if let Some(status) = property("status")? {
+                        if !matches!(status.to_str()?, "okay" | "ok") {
+                            return Ok(None);
+                        }
+                    }
+
+                    Ok(Some(Child {
+                        name,
+                        ptr,
+                        no_map: property("no-map")?.is_some(),
+                        reusable: property("reusable")?.is_some(),
+                    }))
+                })
+                .transpose()
+        })
+    };
+
+    // Static regions.
+    for child in children() {
+        let child = child?;
+        let Some(reg) = optional_property(early_fdt, child.ptr, "reg")? else {
+            continue;
+        };
+
+        let reg = Reg::new(reg, address_cells, size_cells);
+        for entry in reg.entries() {
+            let (address, size) = entry?;
+            let range = Range::from_start_size(address as u64, size as u64)?;
+            reserved_memory.push(child.reserve(memory_map, range)?)?;
+        }
+    }
+
+    // Dynamic regions.
+    for child in children() {
+        let child = child?;
+        let property = |name| optional_property(early_fdt, child.ptr, name);
+
+        if property("reg")?.is_some() {
+            continue;
+        }
+        let Some(size) = property("size")? else {
+            continue;
+        };
+        let size = cells_value(size)?;
+
+        let align = match property("alignment")? {
+            Some(align) => cells_value(align)?,
+            None => PAGE_SIZE as u64,
+        };
+        if !align.is_power_of_two() {
+            return Err(Error::InvalidAlign);
+        }
+
+        // The blocks of a `no-map` region cannot hold any other memory in
+        // use, so whole blocks are allocated.
+        let (alloc_size, alloc_align) = if child.no_map {
+            let alloc_size = size
+                .checked_next_multiple_of(L2_BLOCK_SIZE)
+                .ok_or(Error::NotSatisfiable)?;
+            (alloc_size, max(align, L2_BLOCK_SIZE))
+        } else {
+            (size, align)
+        };
+
+        // Without `alloc-ranges`, the region can be anywhere in RAM.
+        let range = match property("alloc-ranges")? {
+            Some(alloc_ranges) => {
+                let alloc_ranges =
+                    Reg::new(alloc_ranges, address_cells, size_cells);
+                let mut range = None;
+                for entry in alloc_ranges.entries() {
+                    let (address, bounds_size) = entry?;
+                    let bounds = Range::from_start_size(
+                        address as u64,
+                        bounds_size as u64,
+                    )?;
+                    range =
+                        first_fit(memory_map, alloc_size, alloc_align, bounds);
+                    if range.is_some() {
+                        break;
+                    }
+                }
+                range
+            }
+            None => first_fit(
+                memory_map,
+                alloc_size,
+                alloc_align,
+                Range::new(0, u64::MAX)?,
+            ),
+        };
+        let range = range.ok_or(Error::NotSatisfiable)?;
+        let range = Range::from_start_size(range.start(), size)?;
+
+        reserved_memory.push(child.reserve(memory_map, range)?)?;
+    }
+
+    Ok(reserved_memory)
+}
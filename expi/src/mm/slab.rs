@@ -0,0 +1,274 @@
+//! Slab allocator for small objects.
+//!
+//! Small allocations are served from one cache per size class. Every cache
+//! keeps a list of free objects, which are carved from slabs: pages taken
+//! from the [frame allocator](crate::mm::frames). Thus, allocating and
+//! deallocating an object only pops or pushes an element from a list, and
+//! small objects do not fragment the free memory.
+//!
+//! In front of the caches, every core keeps a magazine per size class: a
+//! small stack of free objects that only that core uses. Objects are moved
+//! between the magazines and the caches in batches, so the cores only
+//! contend on the caches when their magazines run empty or full.
+//!
+//! Slabs are never returned to the frame allocator.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::cpu::mp;
+use crate::globals::{GlobalMcsMutex, GlobalMutex, GLOBALS};
+use crate::mm::frames::{self, PAGE_SIZE};
+use crate::mm::Error;
+
+use mutex::McsNode;
+
+/// Size in bytes of the objects of every size class.
+pub const SIZE_CLASSES: [usize; 5] = [32, 64, 128, 256, 512];
+
+/// Size in bytes of a slab. It must be a multiple of every size class.
+pub const SLAB_SIZE: usize = PAGE_SIZE;
+
+/// Maximum number of cores with their own magazines. The cores are
+/// identified by their number in the CPU topology, so the cores whose number
+/// is greater or equal than this value use the caches directly.
+pub const MAX_CORES: usize = 4;
+
+/// Maximum number of objects in a magazine.
+const MAGAZINE_SIZE: usize = 32;
+
+/// Number of objects moved between a magazine and a cache at once.
+const BATCH_SIZE: usize = MAGAZINE_SIZE / 2;
+
+/// Free object. It is stored in the memory of the object itself.
+struct FreeObject {
+    /// Next free object of the same size class.
+    next: Option<NonNull<FreeObject>>,
+}
+
+/// Cache of free objects of a given size class.
+struct SlabCache {
+    /// Size in bytes of the objects.
+    object_size: usize,
+
+    /// First free object.
+    free: Option<NonNull<FreeObject>>,
+}
+
+// The free objects are only accessed while holding the lock of the cache.
+unsafe impl Send for SlabCache {}
+
+impl SlabCache {
+    /// Returns an empty [`SlabCache`] for objects of `object_size` bytes.
+    const fn new(object_size: usize) -> SlabCache {
+        SlabCache {
+            object_size,
+            free: None,
+        }
+    }
+
+    /// Allocates an object. If there are no free objects, a new slab is
+    /// taken from the free memory.
+    fn alloc(&mut self) -> Result<*mut u8, Error> {
+        if self.free.is_none() {
+            self.refill()?;
+        }
+
+        let object = self.free.ok_or(Error::NotSatisfiable)?;
+        self.free = unsafe { object.as_ref().next };
+        Ok(object.as_ptr().cast())
+    }
+
+    /// Returns an object to the cache.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to an object of this cache that is not in use.
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let object = ptr.cast::<FreeObject>();
+        unsafe { object.write(FreeObject { next: self.free }) };
+        self.free = NonNull::new(object);
+    }
+
+    /// Takes a slab from the frame allocator and splits it into free
+    /// objects.
+    fn refill(&mut self) -> Result<(), Error> {
+        let slab = frames::alloc_pages(0)?.start() as usize;
+
+        // Push the objects in reverse order, so they are handed out in
+        // ascending address order.
+        for offset in (0..SLAB_SIZE).step_by(self.object_size).rev() {
+            unsafe { self.dealloc((slab + offset) as *mut u8) };
+        }
+
+        Ok(())
+    }
+}
+
+/// Per-core stack of free objects of a given size class.
+struct Magazine {
+    /// Free objects.
+    objects: [*mut u8; MAGAZINE_SIZE],
+
+    /// Number of free objects in `objects`.
+    len: usize,
+}
+
+// A magazine is only accessed while holding its lock.
+unsafe impl Send for Magazine {}
+
+impl Magazine {
+    /// Returns an empty [`Magazine`].
+    const fn new() -> Magazine {
+        Magazine {
+            objects: [core::ptr::null_mut(); MAGAZINE_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Pops a free object from the magazine.
+    fn pop(&mut self) -> Option<*mut u8> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.objects[self.len])
+    }
+
+    /// Pushes a free object into the magazine, which must not be full.
+    fn push(&mut self, ptr: *mut u8) {
+        self.objects[self.len] = ptr;
+        self.len += 1;
+    }
+
+    /// Moves up to [`BATCH_SIZE`] objects from `cache` into the magazine,
+    /// which must be empty.
+    fn refill(&mut self, cache: &mut SlabCache) -> Result<(), Error> {
+        for _ in 0..BATCH_SIZE {
+            match cache.alloc() {
+                Ok(ptr) => self.push(ptr),
+                // Keep the objects that could be allocated, if any.
+                Err(_) if self.len > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves [`BATCH_SIZE`] objects from the magazine into `cache`. The
+    /// magazine must be full.
+    fn drain(&mut self, cache: &mut SlabCache) {
+        for _ in 0..BATCH_SIZE {
+            let ptr = self.pop().expect("magazine is full");
+            unsafe { cache.dealloc(ptr) };
+        }
+    }
+}
+
+/// Allocator for objects up to the largest size class.
+pub struct SlabAllocator {
+    /// One cache per size class. Every cache has its own lock, so
+    /// allocations of different sizes do not contend.
+    caches: [GlobalMcsMutex<SlabCache>; SIZE_CLASSES.len()],
+
+    /// Magazines of every core, one per size class, indexed by core number.
+    /// They are only locked by their core and by the interrupt handlers
+    /// running on it, which cannot preempt the core while it holds the lock.
+    /// So, these locks are never contended.
+    ///
+    /// Lock order: magazine, cache, free memory.
+    magazines: [[GlobalMutex<Magazine>; SIZE_CLASSES.len()]; MAX_CORES],
+}
+
+impl SlabAllocator {
+    /// Returns a new [`SlabAllocator`] without any slab.
+    pub(crate) const fn new() -> SlabAllocator {
+        SlabAllocator {
+            caches: [
+                GlobalMcsMutex::new(SlabCache::new(SIZE_CLASSES[0])),
+                GlobalMcsMutex::new(SlabCache::new(SIZE_CLASSES[1])),
+                GlobalMcsMutex::new(SlabCache::new(SIZE_CLASSES[2])),
+                GlobalMcsMutex::new(SlabCache::new(SIZE_CLASSES[3])),
+                GlobalMcsMutex::new(SlabCache::new(SIZE_CLASSES[4])),
+            ],
+            magazines: [const {
+                [const { GlobalMutex::new(Magazine::new()) };
+                    SIZE_CLASSES.len()]
+            }; MAX_CORES],
+        }
+    }
+
+    /// Returns the index of the magazines of the current core, which is its
+    /// number in the CPU topology. It returns `None` if the core does not
+    /// have magazines.
+    ///
+    /// The first allocations happen before the CPU topology is available.
+    /// Until then, the caches are used directly.
+    fn magazines_index() -> Option<usize> {
+        let topology = GLOBALS.topology().get()?;
+        let core = topology.core_number(mp::affinity())?;
+        (core < MAX_CORES).then_some(core)
+    }
+
+    /// Returns the index of the size class that can hold an allocation with
+    /// the given layout. It returns `None` if the allocation is bigger than
+    /// the largest size class.
+    ///
+    /// The objects of a size class are aligned to their size, so any
+    /// alignment that fits in the padded size of the layout is honored.
+    pub(crate) fn size_class(layout: &Layout) -> Option<usize> {
+        let size = layout.pad_to_align().size();
+        SIZE_CLASSES.iter().position(|&class| size <= class)
+    }
+
+    /// Allocates an object of the size class `class`.
+    pub(crate) fn alloc(&self, class: usize) -> Result<*mut u8, Error> {
+        let mut node = McsNode::new();
+
+        let Some(core) = Self::magazines_index() else {
+            let mut cache = unsafe { self.caches[class].lock(&mut node) };
+            return cache.alloc();
+        };
+
+        let mut magazine = self.magazines[core][class].lock();
+        if let Some(ptr) = magazine.pop() {
+            return Ok(ptr);
+        }
+
+        let mut cache = unsafe { self.caches[class].lock(&mut node) };
+        magazine.refill(&mut cache)?;
+        drop(cache);
+
+        magazine.pop().ok_or(Error::NotSatisfiable)
+    }
+
+    /// Deallocates an object of the size class `class`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`SlabAllocator::alloc`] with the
+    /// same size class and it must not be used afterwards.
+    pub(crate) unsafe fn dealloc(&self, ptr: *mut u8, class: usize) {
+        let mut node = McsNode::new();
+
+        let Some(core) = Self::magazines_index() else {
+            unsafe { self.caches[class].lock(&mut node).dealloc(ptr) };
+            return;
+        };
+
+        let mut magazine = self.magazines[core][class].lock();
+        if magazine.len == MAGAZINE_SIZE {
+            let mut cache = unsafe { self.caches[class].lock(&mut node) };
+            magazine.drain(&mut cache);
+        }
+        magazine.push(ptr);
+    }
+
+    /// Registers the locks of the caches for debugging.
+    #[cfg(feature = "lock-debug")]
+    pub(crate) fn register(&'static self) {
+        const NAMES: [&str; SIZE_CLASSES.len()] =
+            ["slab_32", "slab_64", "slab_128", "slab_256", "slab_512"];
+
+        for (cache, name) in self.caches.iter().zip(NAMES) {
+            cache.register(name);
+        }
+    }
+}
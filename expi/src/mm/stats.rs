@@ -0,0 +1,136 @@
+//! Allocator statistics.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::mm::slab::{SlabAllocator, SIZE_CLASSES};
+
+/// Number of buckets of the size histogram: one per size class plus one for
+/// the allocations bigger than the largest size class.
+const NBUCKETS: usize = SIZE_CLASSES.len() + 1;
+
+/// Counters updated by the global allocator.
+///
+/// They are atomic, so they can be updated without taking any lock.
+pub struct AllocCounters {
+    /// Number of successful allocations.
+    allocations: AtomicU64,
+
+    /// Number of deallocations.
+    frees: AtomicU64,
+
+    /// Number of failed allocations.
+    failures: AtomicU64,
+
+    /// Bytes requested by the live allocations.
+    bytes_in_use: AtomicU64,
+
+    /// Highest value of `bytes_in_use`.
+    peak_bytes_in_use: AtomicU64,
+
+    /// Number of successful allocations per size class.
+    histogram: [AtomicU64; NBUCKETS],
+}
+
+impl AllocCounters {
+    /// Returns a new [`AllocCounters`] with all the counters set to zero.
+    pub(crate) const fn new() -> AllocCounters {
+        AllocCounters {
+            allocations: AtomicU64::new(0),
+            frees: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            bytes_in_use: AtomicU64::new(0),
+            peak_bytes_in_use: AtomicU64::new(0),
+            histogram: [const { AtomicU64::new(0) }; NBUCKETS],
+        }
+    }
+
+    /// Records an allocation request.
+    pub(crate) fn record_alloc(&self, layout: &Layout, succeeded: bool) {
+        if !succeeded {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let size = layout.size() as u64;
+        let bucket =
+            SlabAllocator::size_class(layout).unwrap_or(SIZE_CLASSES.len());
+
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.histogram[bucket].fetch_add(1, Ordering::Relaxed);
+        let in_use = self.bytes_in_use.fetch_add(size, Ordering::Relaxed);
+        self.peak_bytes_in_use
+            .fetch_max(in_use + size, Ordering::Relaxed);
+    }
+
+    /// Records a deallocation.
+    pub(crate) fn record_dealloc(&self, layout: &Layout) {
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in_use
+            .fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of the counters. The counters are read one
+    /// by one, so the returned values may be inconsistent if other cores are
+    /// allocating memory at the same time.
+    pub fn snapshot(&self) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            bytes_in_use: self.bytes_in_use.load(Ordering::Relaxed),
+            peak_bytes_in_use: self.peak_bytes_in_use.load(Ordering::Relaxed),
+            histogram: core::array::from_fn(|i| {
+                self.histogram[i].load(Ordering::Relaxed)
+            }),
+        }
+    }
+}
+
+/// Snapshot of the allocator statistics.
+///
+/// This structure is created by the [`AllocCounters::snapshot`] method.
+#[derive(Debug, Copy, Clone)]
+pub struct AllocStats {
+    /// Number of successful allocations.
+    pub allocations: u64,
+
+    /// Number of deallocations.
+    pub frees: u64,
+
+    /// Number of failed allocations.
+    pub failures: u64,
+
+    /// Bytes requested by the live allocations.
+    pub bytes_in_use: u64,
+
+    /// Highest value of `bytes_in_use`.
+    pub peak_bytes_in_use: u64,
+
+    /// Number of successful allocations per size class. The last element
+    /// counts the allocations bigger than the largest size class.
+    pub histogram: [u64; NBUCKETS],
+}
+
+impl fmt::Display for AllocStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "allocations: {}", self.allocations)?;
+        writeln!(f, "frees: {}", self.frees)?;
+        writeln!(f, "failed allocations: {}", self.failures)?;
+        writeln!(
+            f,
+            "bytes in use: {} (peak: {})",
+            self.bytes_in_use, self.peak_bytes_in_use
+        )?;
+        for (class, count) in SIZE_CLASSES.iter().zip(self.histogram) {
+            writeln!(f, "  <= {class} bytes: {count}")?;
+        }
+        write!(
+            f,
+            "  > {} bytes: {}",
+            SIZE_CLASSES[SIZE_CLASSES.len() - 1],
+            self.histogram[SIZE_CLASSES.len()]
+        )
+    }
+}
@@ -0,0 +1,150 @@
+//! Tracking of live allocations.
+//!
+//! When the `alloc-tracking` feature is enabled, the global allocator records
+//! the address, the size and the call stack of every live allocation in a
+//! fixed-size table. Then, [`dump_live_allocations`] lists them over the
+//! UART, which helps finding memory leaks.
+//!
+//! The call stack is collected by walking the frame records, so the kernel
+//! must be built with `-Cforce-frame-pointers=yes` to get meaningful
+//! results. The return addresses can be symbolized with `addr2line`.
+
+use core::arch::asm;
+
+use crate::globals::{GlobalMutex, GLOBALS};
+use crate::mm::MemoryKind;
+use crate::println;
+
+/// Maximum number of live allocations that can be tracked.
+const MAX_TRACKED: usize = 1024;
+
+/// Number of return addresses recorded per allocation.
+const TRACE_DEPTH: usize = 4;
+
+/// Live allocation.
+#[derive(Debug, Copy, Clone)]
+struct LiveAllocation {
+    /// Address of the allocation.
+    ptr: usize,
+
+    /// Size in bytes of the allocation.
+    size: usize,
+
+    /// Return addresses of the callers, starting with the innermost one.
+    /// Unused entries are zero.
+    trace: [usize; TRACE_DEPTH],
+}
+
+/// Table of live allocations.
+struct Tracker {
+    /// Live allocations.
+    allocations: [Option<LiveAllocation>; MAX_TRACKED],
+
+    /// Number of allocations that could not be tracked because the table
+    /// was full.
+    untracked: usize,
+}
+
+/// Live allocations recorded by the global allocator.
+static TRACKER: GlobalMutex<Tracker> = GlobalMutex::new(Tracker {
+    allocations: [None; MAX_TRACKED],
+    untracked: 0,
+});
+
+/// Returns the return addresses of the current call stack, walking the chain
+/// of frame records pointed by the frame pointer (x29).
+#[inline(always)]
+fn backtrace() -> [usize; TRACE_DEPTH] {
+    let mut trace = [0; TRACE_DEPTH];
+
+    // Frame records are only followed while they are in memory owned by the
+    // kernel, so a corrupted chain cannot make us read device memory.
+    let Some(memory_map) = GLOBALS.memory_map().get() else {
+        return trace;
+    };
+    let is_valid = |fp: usize| {
+        fp.is_multiple_of(16)
+            && matches!(
+                memory_map.get(fp as u64),
+                Some(
+                    MemoryKind::Ram
+                        | MemoryKind::Kernel
+                        | MemoryKind::BootStack
+                )
+            )
+    };
+
+    let mut fp: usize;
+    unsafe { asm!("mov {}, x29", out(reg) fp) };
+
+    for ret in trace.iter_mut() {
+        if !is_valid(fp) {
+            break;
+        }
+
+        // A frame record is made of the previous frame pointer followed by
+        // the return address.
+        let record = fp as *const [usize; 2];
+        let [prev_fp, lr] = unsafe { record.read() };
+        *ret = lr;
+
+        // The stack grows downwards, so the frame records of the callers
+        // must be at higher addresses.
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+
+    trace
+}
+
+/// Records a new live allocation.
+#[inline(always)]
+pub(crate) fn track(ptr: *mut u8, size: usize) {
+    let allocation = LiveAllocation {
+        ptr: ptr as usize,
+        size,
+        trace: backtrace(),
+    };
+
+    let mut tracker = TRACKER.lock();
+    match tracker.allocations.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => *slot = Some(allocation),
+        None => tracker.untracked += 1,
+    }
+}
+
+/// Removes a live allocation.
+pub(crate) fn untrack(ptr: *mut u8) {
+    let mut tracker = TRACKER.lock();
+    let slot = tracker
+        .allocations
+        .iter_mut()
+        .find(|slot| slot.is_some_and(|a| a.ptr == ptr as usize));
+    if let Some(slot) = slot {
+        *slot = None;
+    }
+}
+
+/// Prints the live allocations with their call stacks.
+///
+/// The tracker lock is not held while printing, so the output may be
+/// inconsistent if other cores are allocating memory at the same time.
+pub fn dump_live_allocations() {
+    let mut total = 0;
+    for i in 0..MAX_TRACKED {
+        let Some(allocation) = TRACKER.lock().allocations[i] else {
+            continue;
+        };
+
+        println!(
+            "{:#010x} {} bytes, allocated from {:#x?}",
+            allocation.ptr, allocation.size, allocation.trace
+        );
+        total += 1;
+    }
+
+    let untracked = TRACKER.lock().untracked;
+    println!("{total} live allocations ({untracked} untracked)");
+}
@@ -12,7 +12,11 @@ use core::ptr::{read_volatile, write_volatile};
 /// ```
 ///
 /// [/arch/arm/boot/dts/bcm2837.dtsi]: https://github.com/raspberrypi/linux/blob/770d94882ac145c81af72e9a37180806c3f70bbd/arch/arm/boot/dts/bcm2837.dtsi#L9-L10
-const MMIO_BASE: usize = 0x3f00_0000;
+pub(crate) const MMIO_BASE: usize = 0x3f00_0000;
+
+/// Size of the MMIO region. It covers both the peripherals and the local
+/// peripherals, which are mapped right after them.
+pub(crate) const MMIO_SIZE: usize = 0x100_1000;
 
 /// Read register. `reg` is the offset of the register from the MMIO base
 /// address.
@@ -21,28 +21,33 @@ impl fmt::Write for UartWriter {
 }
 
 /// Print to the UART.
+///
+/// The output is written once the UART has been initialized by
+/// [`uart::init`](crate::uart::init), which is called by
+/// [`globals::init`](crate::globals::init). Before that, there is no device
+/// to write it to.
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {
         {
-            let mut uart_writer_mg =
-                $crate::globals::GLOBALS.uart_writer().lock();
-            match uart_writer_mg.as_mut() {
-                Some(uart_writer) => {
-                    // The returned `Result` can be safely ignored because
-                    // `UartWriter::write_str` cannot fail.
-                    let _ = core::fmt::Write::write_fmt(
-                        uart_writer,
-                        core::format_args!($($arg)*),
-                    );
-                }
-                None => {}
+            if let Some(uart_writer) =
+                $crate::globals::GLOBALS.uart_writer().get()
+            {
+                let mut node = $crate::mutex::McsNode::new();
+                let mut uart_writer_mg = unsafe { uart_writer.lock(&mut node) };
+
+                // The returned `Result` can be safely ignored because
+                // `UartWriter::write_str` cannot fail.
+                let _ = core::fmt::Write::write_fmt(
+                    &mut *uart_writer_mg,
+                    core::format_args!($($arg)*),
+                );
             }
         }
     };
 }
 
-/// Print to the UART, with a newline.
+/// Print to the UART, with a newline. See [`print!`](crate::print!).
 #[macro_export]
 macro_rules! println {
     () => {
@@ -50,23 +55,6 @@ macro_rules! println {
     };
 
     ($($arg:tt)*) => {
-        {
-            let mut uart_writer_mg =
-                $crate::globals::GLOBALS.uart_writer().lock();
-            match uart_writer_mg.as_mut() {
-                Some(uart_writer) => {
-                    // The returned `Result` can be safely ignored because
-                    // `UartWriter::write_str` cannot fail.
-                    let _ = core::fmt::Write::write_fmt(
-                        uart_writer,
-                        core::format_args!(
-                            "{}\n",
-                            core::format_args!($($arg)*),
-                        ),
-                    );
-                }
-                None => {}
-            }
-        }
+        $crate::print!("{}\n", core::format_args!($($arg)*))
     };
 }
@@ -6,7 +6,7 @@
 
 use core::fmt;
 
-use crate::globals::GLOBALS;
+use crate::globals::{GlobalMcsMutex, GLOBALS};
 use crate::gpio;
 use crate::mailbox;
 use crate::mmio;
@@ -88,14 +88,30 @@ impl fmt::Display for Error {
     }
 }
 
-/// Initializes the UART.
+/// Returns true if the UART has been initialized. It does not take any lock.
+pub fn is_initialized() -> bool {
+    GLOBALS.uart_writer().is_completed()
+}
+
+/// Initializes the UART and sets the UART writer used by the
+/// [`print!`](crate::print!) and [`println!`](crate::println!) macros.
+///
+/// The UART is initialized exactly once. If several cores call this function
+/// at the same time, the others wait until the first one finishes.
 pub fn init() -> Result<(), Error> {
-    let mut uart_writer_mg = GLOBALS.uart_writer().lock();
-    if uart_writer_mg.is_some() {
-        // Already initialized.
-        return Ok(());
-    }
+    let _uart_writer = GLOBALS.uart_writer().try_call_once(|| {
+        configure()?;
+        Ok::<_, Error>(GlobalMcsMutex::new(print::UartWriter))
+    })?;
+
+    #[cfg(feature = "lock-debug")]
+    _uart_writer.register("uart_writer");
 
+    Ok(())
+}
+
+/// Configures the UART hardware.
+fn configure() -> Result<(), Error> {
     unsafe {
         // Mask all UART interrupts. RIMIM, DCDMIM and DSRMIM are unsupported,
         // so we write 0.
@@ -140,9 +156,6 @@ pub fn init() -> Result<(), Error> {
         mmio::write(UARTCR, (1 << 0) | (1 << 8) | (1 << 9));
     }
 
-    // Set globals.
-    *uart_writer_mg = Some(print::UartWriter);
-
     Ok(())
 }
 
@@ -2,14 +2,19 @@
 
 #![no_std]
 #![no_main]
+#![cfg_attr(feature = "arena", feature(allocator_api))]
 
 extern crate alloc;
 
 use alloc::vec;
+use alloc::vec::Vec;
+use core::alloc::Layout;
 
 use expi::globals::GLOBALS;
+use expi::mm;
 use expi::println;
 use expi_macros::entrypoint;
+use mutex::McsNode;
 
 /// Kernel main function.
 #[entrypoint]
@@ -17,7 +22,10 @@ fn kernel_main() {
     println!("expi");
 
     let free_memory_size =
-        GLOBALS.free_memory().lock().as_ref().unwrap().size();
+        unsafe { GLOBALS.free_memory().lock(&mut McsNode::new()) }
+            .as_ref()
+            .unwrap()
+            .size();
 
     println!(
         "free memory: {} MiB",
@@ -26,7 +34,9 @@ fn kernel_main() {
 
     println!(
         "start: {:#x?}",
-        GLOBALS.free_memory().lock().as_ref().unwrap().ranges(),
+        unsafe { GLOBALS.free_memory().lock(&mut McsNode::new()) }
+            .as_ref()
+            .unwrap(),
     );
 
     let mut v = vec![0, 1, 2, 3, 4];
@@ -34,7 +44,9 @@ fn kernel_main() {
 
     println!(
         "after vec: {:#x?}",
-        GLOBALS.free_memory().lock().as_ref().unwrap().ranges(),
+        unsafe { GLOBALS.free_memory().lock(&mut McsNode::new()) }
+            .as_ref()
+            .unwrap(),
     );
 
     v.push(5);
@@ -42,13 +54,39 @@ fn kernel_main() {
 
     println!(
         "after push: {:#x?}",
-        GLOBALS.free_memory().lock().as_ref().unwrap().ranges(),
+        unsafe { GLOBALS.free_memory().lock(&mut McsNode::new()) }
+            .as_ref()
+            .unwrap(),
     );
 
     drop(v);
 
     println!(
         "after drop: {:#x?}",
-        GLOBALS.free_memory().lock().as_ref().unwrap().ranges(),
+        unsafe { GLOBALS.free_memory().lock(&mut McsNode::new()) }
+            .as_ref()
+            .unwrap(),
     );
+    mm::set_oom_hook(oom_hook);
+    let mut big = Vec::<u8>::new();
+    println!("8 GiB reservation: {:?}", big.try_reserve(8 << 30));
+
+    #[cfg(feature = "arena")]
+    {
+        let arena = mm::arena::Arena::new(4096).expect("arena error");
+        let mut v = Vec::new_in(&arena);
+        v.extend_from_slice(&[0, 1, 2, 3, 4]);
+        println!("{v:?} ({} of {} bytes)", arena.used(), arena.capacity());
+    }
+
+    println!("{}", GLOBALS.alloc_counters().snapshot());
+
+    #[cfg(feature = "alloc-tracking")]
+    expi::mm::tracking::dump_live_allocations();
+}
+
+/// Out-of-memory hook. It only reports the failed allocation.
+fn oom_hook(layout: Layout) -> bool {
+    println!("out of memory: {layout:?}");
+    false
 }
@@ -4,10 +4,13 @@
 #![no_main]
 
 use expi::cpu::exceptions::{self, Exception, Interrupt};
-use expi::gpio::{Event, Function, Pin, PullState};
+use expi::cpu::Wfe;
+use expi::gpio::{self, Function, Pin, PullState};
 use expi::intc::{self, IrqSource};
 use expi::println;
+use expi::system_timer;
 use expi_macros::{entrypoint, exception_handler, exception_vector_table};
+use mutex::{Event, SpscQueue};
 
 /// The LED is connected to GPIO26.
 const GPIO_LED: usize = 26;
@@ -15,8 +18,12 @@ const GPIO_LED: usize = 26;
 /// The button is connected to GPIO16.
 const GPIO_BUTTON: usize = 16;
 
-/// Stores if the LED pin is set.
-static mut LED_SET: bool = false;
+/// Set by the GPIO IRQ handler when the button is pressed.
+static BUTTON_PRESSED: Event<Wfe> = Event::new();
+
+/// System Timer counter values of the button presses, pushed by the GPIO
+/// IRQ handler.
+static PRESSES: SpscQueue<u64, 16> = SpscQueue::new();
 
 /// Kernel main function.
 #[entrypoint]
@@ -31,7 +38,7 @@ fn kernel_main() {
     let pin_button = Pin::try_from(GPIO_BUTTON).unwrap();
     pin_button.set_pull_state(PullState::Up);
     pin_button.set_function(Function::Input);
-    pin_button.enable_event(Event::FallingEdge);
+    pin_button.enable_event(gpio::Event::FallingEdge);
 
     // Mask all interrupts.
     Interrupt::SError.mask();
@@ -51,8 +58,24 @@ fn kernel_main() {
     // Enable GPIO interrupts.
     IrqSource::GPIO.enable();
 
-    #[allow(clippy::empty_loop)]
-    loop {}
+    // Toggle the LED every time the button is pressed. The core sleeps
+    // until the IRQ handler signals the event.
+    let mut presses = PRESSES.consumer().unwrap();
+    let mut led_set = false;
+    loop {
+        BUTTON_PRESSED.wait_and_reset();
+
+        for timestamp in presses.by_ref() {
+            println!("button pressed at {timestamp}us");
+
+            if led_set {
+                pin_led.clear();
+            } else {
+                pin_led.set();
+            }
+            led_set = !led_set;
+        }
+    }
 }
 
 /// IRQ handler.
@@ -72,15 +95,12 @@ fn gpio_handler() {
     let pin_button = Pin::try_from(GPIO_BUTTON).unwrap();
     pin_button.clear_event();
 
-    let pin_led = Pin::try_from(GPIO_LED).unwrap();
-    unsafe {
-        if LED_SET {
-            pin_led.clear();
-        } else {
-            pin_led.set();
-        }
-        LED_SET = !LED_SET;
+    // If the queue is full, the press is dropped.
+    if let Some(mut presses) = PRESSES.producer() {
+        let _ = presses.push(system_timer::counter());
     }
+
+    BUTTON_PRESSED.set();
 }
 
 /// Unimplemented exception handler.
@@ -0,0 +1,16 @@
+//! CPU identification.
+
+#![no_std]
+#![no_main]
+
+use expi::cpu::info::CpuInfo;
+use expi::println;
+use expi_macros::entrypoint;
+
+/// Kernel main function.
+#[entrypoint]
+fn kernel_main() {
+    println!("expi");
+
+    println!("{}", CpuInfo::read());
+}
@@ -10,6 +10,7 @@ use expi::globals::GLOBALS;
 use expi::mm;
 use expi::println;
 use expi_macros::entrypoint;
+use mutex::McsNode;
 
 /// FDT example error.
 #[allow(dead_code)]
@@ -62,8 +63,7 @@ fn kernel_main() {
 
 /// Fdt example.
 fn fdt_example() -> Result<(), Error> {
-    let fdt_mg = GLOBALS.fdt().lock();
-    let fdt = fdt_mg.as_ref().ok_or(Error::UninitGlobal)?;
+    let fdt = GLOBALS.fdt().get().ok_or(Error::UninitGlobal)?;
 
     let root = fdt.structure_block().node("/")?;
     let model = root.property("model")?.to_string()?;
@@ -104,8 +104,7 @@ fn fdt_example() -> Result<(), Error> {
 
 /// Fdt iter example.
 fn fdt_iter_example() -> Result<(), Error> {
-    let fdt_mg = GLOBALS.fdt().lock();
-    let fdt = fdt_mg.as_ref().ok_or(Error::UninitGlobal)?;
+    let fdt = GLOBALS.fdt().get().ok_or(Error::UninitGlobal)?;
 
     for node in fdt.structure_block().iter().take(5) {
         println!(
@@ -131,8 +130,7 @@ fn fdt_iter_example() -> Result<(), Error> {
 
 /// EarlyFdt example.
 fn early_fdt_example() -> Result<(), Error> {
-    let fdt_mg = GLOBALS.fdt().lock();
-    let fdt = fdt_mg.as_ref().ok_or(Error::UninitGlobal)?;
+    let fdt = GLOBALS.fdt().get().ok_or(Error::UninitGlobal)?;
 
     let early_fdt = unsafe { EarlyFdt::parse(fdt.header().ptr())? };
 
@@ -162,8 +160,7 @@ fn early_fdt_example() -> Result<(), Error> {
 
 /// EarlyFdt iter example.
 fn early_fdt_iter_example() -> Result<(), Error> {
-    let fdt_mg = GLOBALS.fdt().lock();
-    let fdt = fdt_mg.as_ref().ok_or(Error::UninitGlobal)?;
+    let fdt = GLOBALS.fdt().get().ok_or(Error::UninitGlobal)?;
 
     let early_fdt = unsafe { EarlyFdt::parse(fdt.header().ptr())? };
 
@@ -180,12 +177,12 @@ fn show_free_memory() -> Result<(), Error> {
     let free_mem_size = mm::free_memory_size()? as f32;
     println!("free memory: {} MiB", free_mem_size / 1024.0 / 1024.0);
 
-    let free_memory_mg = GLOBALS.free_memory().lock();
+    let mut node = McsNode::new();
+    let free_memory_mg = unsafe { GLOBALS.free_memory().lock(&mut node) };
     let free_memory = free_memory_mg.as_ref().ok_or(Error::UninitGlobal)?;
 
-    let free_memory_ranges = free_memory.ranges();
-    println!("free memory: {:#x?}", free_memory_ranges);
-    println!("# ranges: {}", free_memory_ranges.len());
+    println!("free memory: {:#x?}", free_memory);
+    println!("# ranges: {}", free_memory.len());
 
     Ok(())
 }
@@ -10,6 +10,6 @@ use expi_macros::entrypoint_mp;
 /// Kernel main function.
 #[entrypoint_mp]
 fn kernel_main() {
-    println!("Hello, core {}!", mp::core_id());
-    println!("Bye, core {}!", mp::core_id());
+    println!("Hello, core {}!", usize::from(mp::core()));
+    println!("Bye, core {}!", usize::from(mp::core()));
 }
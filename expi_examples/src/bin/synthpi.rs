@@ -3,16 +3,19 @@
 #![no_std]
 #![no_main]
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use expi::cpu;
 use expi::cpu::exceptions::{self, Exception, Interrupt};
 use expi::cpu::mp;
-use expi::gpio::{self, Event, Function, Pin, PullState};
+use expi::cpu::Wfe;
+use expi::gpio::{self, Function, Pin, PullState};
 use expi::intc::{self, IrqSource};
 use expi::local_intc::{self, IntSource, IntType};
 use expi::local_timer;
 use expi::println;
 use expi_macros::{entrypoint_mp, exception_handler, exception_vector_table};
-use mutex::TicketMutex;
+use mutex::{Event, SpscQueue};
 
 /// The output pin is GPIO26.
 const GPIO_OUT: usize = 26;
@@ -21,7 +24,14 @@ const GPIO_OUT: usize = 26;
 const GPIO_FREQ_BUTTON: usize = 16;
 
 /// Stores if the output pin is set.
-static OUT_SET: TicketMutex<Option<bool>> = TicketMutex::new(None);
+static OUT_SET: AtomicBool = AtomicBool::new(false);
+
+/// Set by core 0 once the output pin has been configured.
+static OUT_CONFIGURED: Event<Wfe> = Event::new();
+
+/// Local timer reload values requested by core 0's GPIO IRQ handler. They
+/// are applied by core 1's local timer IRQ handler.
+static RELOAD_REQUESTS: SpscQueue<u32, 4> = SpscQueue::new();
 
 /// Local timer reload value for 20Khz.
 const RELOAD_20KHZ: u32 = (19.2e6 / 20e3) as u32;
@@ -34,7 +44,7 @@ const RELOAD_20HZ: u32 = (19.2e6 / 20f32) as u32;
 fn kernel_main() {
     println!("expi");
 
-    match mp::core_id() {
+    match usize::from(mp::core()) {
         0 => configure_global(),
         1 => configure_timer(),
         n => println!("halting core {n}"),
@@ -49,18 +59,14 @@ fn configure_global() {
     // Configure GPIO output.
     let out = Pin::try_from(GPIO_OUT).unwrap();
     out.set_function(Function::Output);
-
-    {
-        let mut out_set = OUT_SET.lock();
-        *out_set = Some(false);
-    }
+    OUT_CONFIGURED.set();
 
     // Configure the GPIO pin of the frequency selecction button.
     IrqSource::GPIO.enable();
     let freq_button = Pin::try_from(GPIO_FREQ_BUTTON).unwrap();
     freq_button.set_pull_state(PullState::Up);
     freq_button.set_function(Function::Input);
-    freq_button.enable_event(Event::FallingEdge);
+    freq_button.enable_event(gpio::Event::FallingEdge);
 
     loop {
         cpu::wfi();
@@ -72,6 +78,9 @@ fn configure_timer() {
     // Configure exceptions.
     configure_exceptions();
 
+    // Wait for core 0 to configure the output pin.
+    OUT_CONFIGURED.wait();
+
     // Configure local timer.
     IntSource::LocalTimer
         .route(mp::core(), IntType::Irq)
@@ -108,7 +117,7 @@ fn configure_exceptions() {
 /// IRQ handler.
 #[exception_handler]
 fn irq_handler() {
-    match mp::core_id() {
+    match usize::from(mp::core()) {
         0 => irq_handler_core0(),
         1 => irq_handler_core1(),
         _ => {}
@@ -137,20 +146,18 @@ fn irq_handler_core1() {
 
 /// Local Timer IRQ handler.
 fn local_timer_handler() {
-    let out = Pin::try_from(GPIO_OUT).unwrap();
-    let mut out_set = OUT_SET.lock();
-
-    match *out_set {
-        None => {}
-        Some(set) => {
-            if set {
-                out.clear();
-            } else {
-                out.set();
-            }
-            *out_set = Some(!set);
+    if let Some(requests) = RELOAD_REQUESTS.consumer() {
+        if let Some(reload) = requests.last() {
+            local_timer::set_reload_value(reload);
         }
     }
+
+    let out = Pin::try_from(GPIO_OUT).unwrap();
+    if OUT_SET.fetch_xor(true, Ordering::Relaxed) {
+        out.clear();
+    } else {
+        out.set();
+    }
 }
 
 /// GPIO IRQ handler.
@@ -163,7 +170,7 @@ fn gpio_handler() {
     }
 }
 
-/// Select next frequency value.
+/// Select next frequency value. The new value is applied by core 1.
 fn next_freq() {
     let reload = local_timer::reload_value();
     let val = if reload < RELOAD_20HZ {
@@ -171,7 +178,11 @@ fn next_freq() {
     } else {
         RELOAD_20KHZ
     };
-    local_timer::set_reload_value(val);
+
+    // If the queue is full, the request is dropped.
+    if let Some(mut requests) = RELOAD_REQUESTS.producer() {
+        let _ = requests.push(val);
+    }
 }
 
 /// Unimplemented exception handler.
@@ -44,9 +44,9 @@ pub fn entrypoint(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 // Save dtb_ptr32 into a callee-saved register.
                 mov x19, x0
 
-                // Allocate an initial stack of approximately 0x80000
-                // bytes. This is a temporary stack used by init functions.
-                ldr x0, =0x80000
+                // Allocate an initial stack right below the kernel. This is
+                // a temporary stack used by init functions.
+                ldr x0, ={{kernel_base}}
                 mov sp, x0
 
                 // Initialize MMU.
@@ -78,9 +78,11 @@ pub fn entrypoint(_attr: TokenStream, item: TokenStream) -> TokenStream {
         extern "C" fn _expi_globals_init(dtb_ptr32: u32) -> u64 {
             expi::globals::init(dtb_ptr32).expect("init error");
 
-            let end = expi::globals::GLOBALS
-                .free_memory()
-                .lock()
+            let mut node = expi::mutex::McsNode::new();
+            let mut free_memory_mg = unsafe {
+                expi::globals::GLOBALS.free_memory().lock(&mut node)
+            };
+            let end = free_memory_mg
                 .as_mut()
                 .expect("uninitialized allocator")
                 .end()
@@ -93,7 +95,10 @@ pub fn entrypoint(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #[unsafe(no_mangle)]
         #[unsafe(naked)]
         unsafe extern "C" fn _start() -> ! {
-            core::arch::naked_asm!(#start_code)
+            core::arch::naked_asm!(
+                #start_code,
+                kernel_base = const expi::mm::KERNEL_BASE,
+            )
         }
 
         #[unsafe(no_mangle)]
@@ -112,7 +117,10 @@ const CORE_STACK_SIZE: usize = 32 * 1024 * 1024;
 
 /// The multi-processing version of [`macro@entrypoint`].
 ///
-/// It boots the four cores allocating a fixed size stack for each one.
+/// It boots all the cores described in the devicetree allocating a fixed size
+/// stack for each one. The secondary cores are released via the spin-table
+/// addresses advertised in the devicetree and brought up following the
+/// sequence described in `expi::cpu::mp`.
 #[proc_macro_attribute]
 pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let item_fn = parse_macro_input!(item as ItemFn);
@@ -120,30 +128,38 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let fname_rust = &item_fn.sig.ident;
     let fname_c = format_ident!("_expi_c_{}", fname_rust);
 
-    let start_mp_code = format!(
+    let start_mp_code = r#"
+            // Get core ID.
+            mrs x20, mpidr_el1
+            and x20, x20, #0xff
+
+            // Allocate a temporary stack for the secondary core bring-up.
+            // They are indexed by core ID and placed below the kernel.
+            // `release_secondaries` checks that they fit in the boot stacks
+            // region.
+            ldr x0, ={boot_stack_size}
+            ldr x1, ={kernel_base}
+            add x2, x20, #1
+            mul x2, x2, x0
+            sub x2, x1, x2
+            mov sp, x2
+            bl _expi_mp_init_secondary
+
+            b _expi_start_mp_main
+        "#;
+
+    let start_mp_main_code = format!(
         r#"
-                // Get stack top address from 0x1000.
-                ldr x0, =0x1000
-                ldr x19, [x0], 0x8
-
-                // Get core ID.
-                mrs x20, mpidr_el1
-                and x20, x20, #0xff
-
-                // Core 0's MMU is already initialized, so skip initialization.
-                cbz x20, 1f
-
-                // Allocate an initial stack of approximately 0x10000 bytes.
-                // This is a temporary stack used for mmu initialization.
-                ldr x0, =0x10000
-                ldr x1, =0x80000
-                add x2, x20, #1
-                mul x2, x2, x0
-                sub x2, x1, x2
-                mov sp, x2
-                bl _expi_enable_identity_mapping
+                // Get stack top address from the stack top slot. The MMU of
+                // the current core is enabled and its data cache is coherent
+                // with the other cores, so no cache maintenance is required.
+                ldr x0, ={{stack_top_slot}}
+                ldr x19, [x0]
+
+                // Get core number.
+                bl _expi_mp_core_number
+                mov x20, x0
 
-            1:
                 // Load stack size.
                 ldr x0, ={CORE_STACK_SIZE:#x}
 
@@ -156,9 +172,9 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 // Call kernel main.
                 bl {fname_c}
 
-            2:
+            1:
                 wfe
-                b 2b
+                b 1b
         "#
     );
 
@@ -172,9 +188,11 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
         extern "C" fn _expi_globals_init(dtb_ptr32: u32) -> u64 {
             expi::globals::init(dtb_ptr32).expect("init error");
 
-            let end = expi::globals::GLOBALS
-                .free_memory()
-                .lock()
+            let mut node = expi::mutex::McsNode::new();
+            let mut free_memory_mg = unsafe {
+                expi::globals::GLOBALS.free_memory().lock(&mut node)
+            };
+            let end = free_memory_mg
                 .as_mut()
                 .expect("uninitialized allocator")
                 .end()
@@ -184,8 +202,19 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
 
         #[unsafe(no_mangle)]
-        extern "C" fn _expi_dcache_clean_inval() {
-            unsafe { expi::cpu::mmu::dcache_clean_inval_poc(0, 0x2000) };
+        extern "C" fn _expi_mp_release_secondaries(entry: usize) {
+            unsafe { expi::cpu::mp::release_secondaries(entry) }
+                .expect("could not release secondary cores");
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn _expi_mp_init_secondary() {
+            unsafe { expi::cpu::mp::init_secondary() };
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn _expi_mp_core_number() -> usize {
+            usize::from(expi::cpu::mp::core())
         }
 
         #[link_section = ".entry"]
@@ -197,9 +226,9 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     // Save dtb_ptr32 into a callee-saved register.
                     mov x19, x0
 
-                    // Allocate an initial stack of approximately 0x80000
-                    // bytes. This is a temporary stack used by init functions.
-                    ldr x0, =0x80000
+                    // Allocate an initial stack right below the kernel. This
+                    // is a temporary stack used by init functions.
+                    ldr x0, ={kernel_base}
                     mov sp, x0
 
                     // Initialize MMU.
@@ -209,36 +238,42 @@ pub fn entrypoint_mp(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     mov x0, x19
                     bl _expi_globals_init
 
-                    // Save stack top address at 0x1000.
-                    ldr x1, =0x1000
-                    str x0, [x1], 0x8
+                    // Save stack top address into the stack top slot.
+                    ldr x1, ={stack_top_slot}
+                    str x0, [x1]
 
-                    // All cores but core 0 are waiting for a wakeup event.
-                    // Once the event is received, they jump to the address
-                    // stored at 0xe0 (core 1), 0xe8 (core 2) and 0xf0 (core 3)
-                    // if not zero. Implementation:
-                    // https://github.com/raspberrypi/tools/blob/master/armstubs/armstub8.S
+                    // Release the secondary cores. They will jump to
+                    // _expi_start_mp.
                     adr x0, _expi_start_mp
-                    mov x1, #0xe0
-                    str x0, [x1], #0x8
-                    str x0, [x1], #0x8
-                    str x0, [x1], #0x8
-
-                    // Clean and invalidate the first two pages. This is
-                    // required because several global variables used during
-                    // multi-processing intialization live there.
-                    bl _expi_dcache_clean_inval
-
-                    sev
-
-                    b _expi_start_mp
-                "#)
+                    bl _expi_mp_release_secondaries
+
+                    // The MMU of the boot core is already initialized, so
+                    // skip the secondary core bring-up.
+                    b _expi_start_mp_main
+                "#,
+                kernel_base = const expi::mm::KERNEL_BASE,
+                stack_top_slot = const expi::mm::STACK_TOP_SLOT,
+            )
         }
 
         #[unsafe(no_mangle)]
         #[unsafe(naked)]
         unsafe extern "C" fn _expi_start_mp() -> ! {
-            core::arch::naked_asm!(#start_mp_code)
+            core::arch::naked_asm!(
+                #start_mp_code,
+                kernel_base = const expi::mm::KERNEL_BASE,
+                boot_stack_size =
+                    const expi::cpu::mp::SECONDARY_BOOT_STACK_SIZE,
+            )
+        }
+
+        #[unsafe(no_mangle)]
+        #[unsafe(naked)]
+        unsafe extern "C" fn _expi_start_mp_main() -> ! {
+            core::arch::naked_asm!(
+                #start_mp_main_code,
+                stack_top_slot = const expi::mm::STACK_TOP_SLOT,
+            )
         }
 
         #[unsafe(no_mangle)]
@@ -0,0 +1,157 @@
+//! Condition variable.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::wait::{Spin, WaitHint};
+use crate::TicketMutexGuard;
+
+/// A condition variable.
+///
+/// It allows waiting for the data protected by a
+/// [`TicketMutex`](crate::TicketMutex) to change without holding the lock.
+/// The waiting processors wait with `W` until they are notified.
+///
+/// As with any condition variable, waiters must check their condition again
+/// after waking up, which is done by [`Condvar::wait_while`].
+pub struct Condvar<W: WaitHint = Spin> {
+    /// Number of notifications. Waiters wait until it changes.
+    seq: AtomicUsize,
+
+    /// Waiting strategy.
+    _wait: PhantomData<W>,
+}
+
+impl<W: WaitHint> Condvar<W> {
+    /// Returns a new [`Condvar`].
+    pub const fn new() -> Condvar<W> {
+        Condvar {
+            seq: AtomicUsize::new(0),
+            _wait: PhantomData,
+        }
+    }
+
+    /// Releases the lock held by `guard`, waits until the condition variable
+    /// is notified and locks the mutex again.
+    ///
+    /// It may return spuriously.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn wait<'a, T>(
+        &self,
+        guard: TicketMutexGuard<'a, T>,
+    ) -> TicketMutexGuard<'a, T> {
+        // Read the sequence number while holding the lock, so notifications
+        // issued after releasing it are not lost.
+        let seq = self.seq.load(Ordering::Acquire);
+
+        let mutex = guard.mutex;
+        drop(guard);
+
+        while self.seq.load(Ordering::Acquire) == seq {
+            W::wait()
+        }
+
+        mutex.lock()
+    }
+
+    /// Waits until `condition` returns false. The lock is released while
+    /// waiting.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: TicketMutexGuard<'a, T>,
+        mut condition: F,
+    ) -> TicketMutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Wakes up one of the processors waiting on the condition variable.
+    ///
+    /// Waiters are not tracked individually, so every waiter is woken up.
+    /// All of them but one will usually find their condition unchanged and
+    /// go back to wait.
+    pub fn notify_one(&self) {
+        self.notify_all()
+    }
+
+    /// Wakes up all the processors waiting on the condition variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        W::wake();
+    }
+}
+
+impl<W: WaitHint> Default for Condvar<W> {
+    fn default() -> Self {
+        Condvar::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::TicketMutex;
+
+    #[test]
+    fn condvar_wait_while() {
+        let pair = Arc::new((TicketMutex::new(false), Condvar::<Spin>::new()));
+
+        let notifier = {
+            let pair = Arc::clone(&pair);
+            thread::spawn(move || {
+                let (mutex, condvar) = &*pair;
+                *mutex.lock() = true;
+                condvar.notify_one();
+            })
+        };
+
+        let (mutex, condvar) = &*pair;
+        let ready = condvar.wait_while(mutex.lock(), |ready| !*ready);
+        assert!(*ready);
+        drop(ready);
+
+        notifier.join().unwrap();
+    }
+
+    #[test]
+    fn condvar_producer_consumer() {
+        const NITERS: usize = 1_000;
+
+        let pair = Arc::new((TicketMutex::new(0usize), Condvar::<Spin>::new()));
+
+        let producer = {
+            let pair = Arc::clone(&pair);
+            thread::spawn(move || {
+                let (mutex, condvar) = &*pair;
+                for _ in 0..NITERS {
+                    *mutex.lock() += 1;
+                    condvar.notify_all();
+                }
+            })
+        };
+
+        let (mutex, condvar) = &*pair;
+        let mut consumed = 0;
+        while consumed < NITERS {
+            let mut items =
+                condvar.wait_while(mutex.lock(), |items| *items == 0);
+            consumed += *items;
+            *items = 0;
+        }
+
+        producer.join().unwrap();
+        assert_eq!(consumed, NITERS);
+    }
+}
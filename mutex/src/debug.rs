@@ -0,0 +1,474 @@
+//! Lock debugging.
+//!
+//! When the `debug` feature is enabled, [`TicketMutex`](crate::TicketMutex)
+//! and [`McsMutex`](crate::McsMutex) keep track of:
+//!
+//! - The current owner of the lock: the core that holds it and the location
+//!   where it was acquired.
+//! - Contention statistics: number of acquisitions, number of contended
+//!   acquisitions, spin counts and hold times.
+//!
+//! Locking a mutex that is already held by the current core would spin
+//! forever. Instead, it panics reporting both acquisition sites.
+//!
+//! This crate does not know how to identify the current core or how to
+//! measure time, so these operations are provided by the user via
+//! [`set_hooks`]. Until then, owners are recorded without core and hold
+//! times are not measured.
+//!
+//! Mutexes with `'static` lifetime can be added to a registry with their
+//! `register` method. The registered locks can be inspected at any time with
+//! [`locks`], even while the system is deadlocked, because reading the debug
+//! information does not take any lock.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{
+    AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering,
+};
+
+/// Value of [`LockDebug::owner`] when the core is unknown.
+const UNKNOWN_CORE: usize = usize::MAX;
+
+/// Hooks used to collect debug information.
+#[derive(Debug)]
+pub struct DebugHooks {
+    /// Returns an identifier of the current core.
+    pub core_id: fn() -> usize,
+
+    /// Returns the current value of a monotonic clock. Hold times are
+    /// expressed in the units of this clock.
+    pub now: fn() -> u64,
+}
+
+/// Hooks provided by the user.
+static HOOKS: AtomicPtr<DebugHooks> = AtomicPtr::new(ptr::null_mut());
+
+/// Head of the list of registered locks.
+static REGISTRY: AtomicPtr<LockDebug> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the hooks used to collect debug information.
+pub fn set_hooks(hooks: &'static DebugHooks) {
+    HOOKS.store(hooks as *const DebugHooks as *mut _, Ordering::Release);
+}
+
+/// Returns the hooks provided by the user, if any.
+fn hooks() -> Option<&'static DebugHooks> {
+    unsafe { HOOKS.load(Ordering::Acquire).as_ref() }
+}
+
+/// Current owner of a lock.
+#[derive(Debug, Copy, Clone)]
+pub struct LockOwner {
+    /// Core holding the lock. It is [`None`] if no core identification hook
+    /// was set when the lock was acquired.
+    pub core: Option<usize>,
+
+    /// Location where the lock was acquired.
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.core {
+            Some(core) => write!(f, "core {core} at {}", self.location),
+            None => write!(f, "unknown core at {}", self.location),
+        }
+    }
+}
+
+/// Contention statistics of a lock.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LockStats {
+    /// Number of times the lock has been acquired.
+    pub acquisitions: u64,
+
+    /// Number of acquisitions that had to wait for the lock.
+    pub contentions: u64,
+
+    /// Total number of spins while waiting for the lock.
+    pub spins: u64,
+
+    /// Maximum number of spins of a single acquisition.
+    pub max_spins: u64,
+
+    /// Total time the lock has been held.
+    pub hold_time: u64,
+
+    /// Maximum time the lock has been held by a single acquisition.
+    pub max_hold_time: u64,
+}
+
+impl fmt::Display for LockStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "acquisitions={} contentions={} spins={} max_spins={} \
+             hold_time={} max_hold_time={}",
+            self.acquisitions,
+            self.contentions,
+            self.spins,
+            self.max_spins,
+            self.hold_time,
+            self.max_hold_time,
+        )
+    }
+}
+
+/// Debug information of a registered lock.
+#[derive(Debug, Copy, Clone)]
+pub struct LockInfo {
+    /// Name given on registration.
+    pub name: &'static str,
+
+    /// Current owner of the lock.
+    pub owner: Option<LockOwner>,
+
+    /// Contention statistics.
+    pub stats: LockStats,
+}
+
+impl fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.owner {
+            Some(owner) => write!(f, "{}: held by {owner}", self.name)?,
+            None => write!(f, "{}: free", self.name)?,
+        }
+        write!(f, " ({})", self.stats)
+    }
+}
+
+/// Returns an iterator over the debug information of the registered locks.
+///
+/// The information is read without taking the locks, so the owner and the
+/// statistics of a lock that is being acquired or released concurrently may
+/// be slightly inconsistent.
+pub fn locks() -> Locks {
+    Locks {
+        next: REGISTRY.load(Ordering::Acquire),
+    }
+}
+
+/// Iterator over the debug information of the registered locks.
+///
+/// This structure is created by the [`locks`] function.
+pub struct Locks {
+    /// Next lock.
+    next: *const LockDebug,
+}
+
+impl Iterator for Locks {
+    type Item = LockInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let debug = unsafe { self.next.as_ref()? };
+        self.next = debug.next.load(Ordering::Acquire);
+        Some(debug.info())
+    }
+}
+
+/// Debug information embedded into every mutex.
+pub(crate) struct LockDebug {
+    /// Core holding the lock. It is only valid if `location` is not null.
+    owner: AtomicUsize,
+
+    /// Location where the lock was acquired. It is null if the lock is free.
+    location: AtomicPtr<Location<'static>>,
+
+    /// Time when the lock was acquired.
+    acquired_at: AtomicU64,
+
+    /// See [`LockStats::acquisitions`].
+    acquisitions: AtomicU64,
+
+    /// See [`LockStats::contentions`].
+    contentions: AtomicU64,
+
+    /// See [`LockStats::spins`].
+    spins: AtomicU64,
+
+    /// See [`LockStats::max_spins`].
+    max_spins: AtomicU64,
+
+    /// See [`LockStats::hold_time`].
+    hold_time: AtomicU64,
+
+    /// See [`LockStats::max_hold_time`].
+    max_hold_time: AtomicU64,
+
+    /// True if the lock has been registered.
+    registered: AtomicBool,
+
+    /// Name given on registration. It is written once before the lock is
+    /// published in the registry.
+    name: UnsafeCell<&'static str>,
+
+    /// Next lock in the registry.
+    next: AtomicPtr<LockDebug>,
+}
+
+unsafe impl Sync for LockDebug {}
+
+impl LockDebug {
+    /// Returns a new [`LockDebug`].
+    pub(crate) const fn new() -> LockDebug {
+        LockDebug {
+            owner: AtomicUsize::new(UNKNOWN_CORE),
+            location: AtomicPtr::new(ptr::null_mut()),
+            acquired_at: AtomicU64::new(0),
+            acquisitions: AtomicU64::new(0),
+            contentions: AtomicU64::new(0),
+            spins: AtomicU64::new(0),
+            max_spins: AtomicU64::new(0),
+            hold_time: AtomicU64::new(0),
+            max_hold_time: AtomicU64::new(0),
+            registered: AtomicBool::new(false),
+            name: UnsafeCell::new(""),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Adds the lock to the registry with the provided `name`. Registering a
+    /// lock more than once has no effect.
+    pub(crate) fn register(&'static self, name: &'static str) {
+        if self.registered.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        unsafe { *self.name.get() = name };
+
+        let this = self as *const LockDebug as *mut LockDebug;
+        let mut head = REGISTRY.load(Ordering::Acquire);
+        loop {
+            self.next.store(head, Ordering::Relaxed);
+            match REGISTRY.compare_exchange_weak(
+                head,
+                this,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Panics if the lock is already held by the current core, which would
+    /// never be able to acquire it.
+    pub(crate) fn check_recursion(&self, location: &'static Location<'static>) {
+        let Some(hooks) = hooks() else {
+            return;
+        };
+
+        let held = self.location.load(Ordering::Acquire);
+        if held.is_null() {
+            return;
+        }
+
+        let core = (hooks.core_id)();
+        if self.owner.load(Ordering::Acquire) == core {
+            let held = unsafe { &*held };
+            panic!(
+                "recursive lock on core {core}: lock acquired at {held}, \
+                 requested again at {location}"
+            );
+        }
+    }
+
+    /// Records that the lock has been acquired at `location` after spinning
+    /// `spins` times.
+    pub(crate) fn acquired(
+        &self,
+        location: &'static Location<'static>,
+        spins: u64,
+    ) {
+        let (core, now) = match hooks() {
+            Some(hooks) => ((hooks.core_id)(), (hooks.now)()),
+            None => (UNKNOWN_CORE, 0),
+        };
+
+        self.owner.store(core, Ordering::Relaxed);
+        self.acquired_at.store(now, Ordering::Relaxed);
+        self.location.store(
+            location as *const Location as *mut Location,
+            Ordering::Release,
+        );
+
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if spins > 0 {
+            self.contentions.fetch_add(1, Ordering::Relaxed);
+            self.spins.fetch_add(spins, Ordering::Relaxed);
+            self.max_spins.fetch_max(spins, Ordering::Relaxed);
+        }
+    }
+
+    /// Records that the lock is being released. It must be called before
+    /// the lock is actually released.
+    pub(crate) fn released(&self) {
+        if let Some(hooks) = hooks() {
+            let acquired_at = self.acquired_at.load(Ordering::Relaxed);
+            let hold_time = (hooks.now)().wrapping_sub(acquired_at);
+            self.hold_time.fetch_add(hold_time, Ordering::Relaxed);
+            self.max_hold_time.fetch_max(hold_time, Ordering::Relaxed);
+        }
+
+        self.location.store(ptr::null_mut(), Ordering::Release);
+        self.owner.store(UNKNOWN_CORE, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the debug information of the lock.
+    pub(crate) fn info(&self) -> LockInfo {
+        let location = self.location.load(Ordering::Acquire);
+        let owner = unsafe { location.as_ref() }.map(|location| {
+            let core = self.owner.load(Ordering::Relaxed);
+            LockOwner {
+                core: (core != UNKNOWN_CORE).then_some(core),
+                location,
+            }
+        });
+
+        let stats = LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contentions: self.contentions.load(Ordering::Relaxed),
+            spins: self.spins.load(Ordering::Relaxed),
+            max_spins: self.max_spins.load(Ordering::Relaxed),
+            hold_time: self.hold_time.load(Ordering::Relaxed),
+            max_hold_time: self.max_hold_time.load(Ordering::Relaxed),
+        };
+
+        let name = if self.registered.load(Ordering::Acquire) {
+            unsafe { *self.name.get() }
+        } else {
+            ""
+        };
+
+        LockInfo { name, owner, stats }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::{McsMutex, McsNode, TicketMutex};
+
+    /// Returns a per-thread identifier that plays the role of a core ID.
+    fn test_core_id() -> usize {
+        std::thread_local! {
+            static ID: Cell<usize> = const { Cell::new(usize::MAX) };
+        }
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        ID.with(|id| {
+            if id.get() == usize::MAX {
+                id.set(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+            }
+            id.get()
+        })
+    }
+
+    /// Clock that advances one tick per call.
+    fn test_now() -> u64 {
+        static CLOCK: AtomicU64 = AtomicU64::new(0);
+        CLOCK.fetch_add(1, Ordering::Relaxed)
+    }
+
+    static TEST_HOOKS: DebugHooks = DebugHooks {
+        core_id: test_core_id,
+        now: test_now,
+    };
+
+    #[test]
+    fn debug_owner_and_stats() {
+        set_hooks(&TEST_HOOKS);
+
+        let mutex = TicketMutex::new(0);
+        let x = mutex.lock();
+        let info = mutex.debug.info();
+        let owner = info.owner.unwrap();
+        assert_eq!(owner.core, Some(test_core_id()));
+        assert_eq!(owner.location.file(), file!());
+        drop(x);
+
+        let info = mutex.debug.info();
+        assert!(info.owner.is_none());
+        assert_eq!(info.stats.acquisitions, 1);
+        assert_eq!(info.stats.contentions, 0);
+        assert!(info.stats.hold_time > 0);
+    }
+
+    #[test]
+    fn debug_contention() {
+        set_hooks(&TEST_HOOKS);
+
+        const NTHREADS: usize = 4;
+        const NITERS: usize = 1_000;
+
+        let mutex = Arc::new(TicketMutex::new(0));
+
+        let handles = (0..NTHREADS)
+            .map(|_| {
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    for _ in 0..NITERS {
+                        *mutex.lock() += 1;
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = mutex.debug.info().stats;
+        assert_eq!(stats.acquisitions, (NTHREADS * NITERS) as u64);
+        assert!(stats.contentions <= stats.spins);
+        assert!(stats.max_spins <= stats.spins);
+    }
+
+    #[test]
+    #[should_panic(expected = "recursive lock")]
+    fn debug_recursive_ticket_lock() {
+        set_hooks(&TEST_HOOKS);
+
+        let mutex = TicketMutex::new(0);
+        let _x = mutex.lock();
+        let _y = mutex.lock();
+    }
+
+    #[test]
+    #[should_panic(expected = "recursive lock")]
+    fn debug_recursive_mcs_lock() {
+        set_hooks(&TEST_HOOKS);
+
+        let mutex = McsMutex::new(0);
+        let mut node_x = McsNode::new();
+        let mut node_y = McsNode::new();
+        let _x = unsafe { mutex.lock(&mut node_x) };
+        let _y = unsafe { mutex.lock(&mut node_y) };
+    }
+
+    #[test]
+    fn debug_registry() {
+        static MUTEX: TicketMutex<u32> = TicketMutex::new(0);
+        MUTEX.register("test_registry");
+        MUTEX.register("test_registry");
+
+        let _x = MUTEX.lock();
+
+        let registered = locks()
+            .filter(|info| info.name == "test_registry")
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(registered.len(), 1);
+        assert!(registered[0].owner.is_some());
+    }
+}
@@ -0,0 +1,108 @@
+//! Event flag.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::wait::{Spin, WaitHint};
+
+/// A manual-reset event.
+///
+/// Processors calling [`Event::wait`] wait with `W` until the event is set.
+/// The event stays set until [`Event::reset`] is called. It is useful to
+/// signal from an interrupt handler that data is ready.
+pub struct Event<W: WaitHint = Spin> {
+    /// True if the event is set.
+    set: AtomicBool,
+
+    /// Waiting strategy.
+    _wait: PhantomData<W>,
+}
+
+impl<W: WaitHint> Event<W> {
+    /// Returns a new [`Event`] that is not set.
+    pub const fn new() -> Event<W> {
+        Event {
+            set: AtomicBool::new(false),
+            _wait: PhantomData,
+        }
+    }
+
+    /// Sets the event and wakes the waiting processors.
+    pub fn set(&self) {
+        self.set.store(true, Ordering::Release);
+        W::wake();
+    }
+
+    /// Clears the event.
+    pub fn reset(&self) {
+        self.set.store(false, Ordering::Release);
+    }
+
+    /// Returns true if the event is set.
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    /// Waits until the event is set.
+    pub fn wait(&self) {
+        while !self.is_set() {
+            W::wait()
+        }
+    }
+
+    /// Waits until the event is set and clears it. If several processors
+    /// are waiting, only one of them returns.
+    pub fn wait_and_reset(&self) {
+        while self
+            .set
+            .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            W::wait()
+        }
+    }
+}
+
+impl<W: WaitHint> Default for Event<W> {
+    fn default() -> Self {
+        Event::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn event_set_reset() {
+        let event = Event::<Spin>::new();
+        assert!(!event.is_set());
+
+        event.set();
+        event.wait();
+        assert!(event.is_set());
+
+        event.reset();
+        assert!(!event.is_set());
+    }
+
+    #[test]
+    fn event_wait_and_reset() {
+        let event = Arc::new(Event::<Spin>::new());
+
+        let setter = {
+            let event = Arc::clone(&event);
+            thread::spawn(move || event.set())
+        };
+
+        event.wait_and_reset();
+        assert!(!event.is_set());
+
+        setter.join().unwrap();
+    }
+}
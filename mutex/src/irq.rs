@@ -0,0 +1,414 @@
+//! Mutexes that mask interrupts while they are held.
+//!
+//! A spin lock taken by both regular code and an interrupt handler deadlocks
+//! if the interrupt arrives while the interrupted code holds the lock on the
+//! same processor: the handler spins forever waiting for a lock that can only
+//! be released once the handler returns. The mutexes in this module mask
+//! interrupts before acquiring the lock and restore the previous interrupt
+//! state after releasing it, so the handler can never run in between.
+//!
+//! This crate is architecture independent. The code that saves, masks and
+//! restores the interrupt state is provided by the user through the
+//! [`IrqControl`] trait.
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::{
+    McsMutex, McsMutexGuard, McsNode, RwLock, RwLockReadGuard,
+    RwLockWriteGuard, TicketMutex, TicketMutexGuard,
+};
+
+/// Saves, masks and restores the interrupt state of the current processor.
+///
+/// # Safety
+///
+/// Implementors must guarantee that no interrupt handler that may take an
+/// interrupt-safe mutex can run on the current processor between a call to
+/// [`IrqControl::save_and_mask`] and the matching call to
+/// [`IrqControl::restore`].
+pub unsafe trait IrqControl {
+    /// Interrupt state saved by [`IrqControl::save_and_mask`].
+    type State: Copy;
+
+    /// Saves the interrupt state of the current processor, masks interrupts
+    /// and returns the saved state.
+    fn save_and_mask() -> Self::State;
+
+    /// Restores the interrupt state returned by
+    /// [`IrqControl::save_and_mask`].
+    fn restore(state: Self::State);
+}
+
+/// Interrupt state saved when an interrupt-safe mutex is locked. It is
+/// restored when this structure is dropped.
+struct SavedIrqState<I: IrqControl> {
+    /// Saved interrupt state.
+    state: I::State,
+}
+
+impl<I: IrqControl> SavedIrqState<I> {
+    /// Saves the interrupt state and masks interrupts.
+    fn save_and_mask() -> SavedIrqState<I> {
+        SavedIrqState {
+            state: I::save_and_mask(),
+        }
+    }
+}
+
+impl<I: IrqControl> Drop for SavedIrqState<I> {
+    fn drop(&mut self) {
+        I::restore(self.state);
+    }
+}
+
+/// A [`TicketMutex`] that masks interrupts while it is held.
+///
+/// The interrupt state is saved and interrupts are masked before trying to
+/// acquire the lock. The saved state is restored after the lock is released.
+pub struct IrqSafeMutex<T, I: IrqControl> {
+    /// Underlying mutex.
+    mutex: TicketMutex<T>,
+
+    /// Interrupt control.
+    _irq: PhantomData<I>,
+}
+
+/// An RAII implementation of a "scoped lock" on an interrupt-safe mutex. When
+/// this structure is dropped, the lock will be unlocked and the interrupt
+/// state will be restored.
+///
+/// This structure is created by the [`IrqSafeMutex::lock`] method.
+pub struct IrqSafeMutexGuard<'a, T, I: IrqControl> {
+    /// Guard of the underlying mutex.
+    ///
+    /// Fields are dropped in declaration order, so the lock is released
+    /// before the interrupt state is restored.
+    guard: TicketMutexGuard<'a, T>,
+
+    /// Interrupt state saved on lock.
+    _saved: SavedIrqState<I>,
+}
+
+impl<T, I: IrqControl> IrqSafeMutex<T, I> {
+    /// Returns a new [`IrqSafeMutex`] protecting `data`.
+    pub const fn new(data: T) -> IrqSafeMutex<T, I> {
+        IrqSafeMutex {
+            mutex: TicketMutex::new(data),
+            _irq: PhantomData,
+        }
+    }
+
+    /// Masks interrupts, locks the mutex and returns an
+    /// [`IrqSafeMutexGuard`] that grants exclusive access to the protected
+    /// data until it is dropped.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn lock(&self) -> IrqSafeMutexGuard<'_, T, I> {
+        let saved = SavedIrqState::save_and_mask();
+        IrqSafeMutexGuard {
+            guard: self.mutex.lock(),
+            _saved: saved,
+        }
+    }
+
+    /// Adds the mutex to the registry of locks with the provided `name`. See
+    /// [`debug::locks`](crate::debug::locks).
+    #[cfg(feature = "debug")]
+    pub fn register(&'static self, name: &'static str) {
+        self.mutex.register(name);
+    }
+}
+
+unsafe impl<T: Send, I: IrqControl> Send for IrqSafeMutex<T, I> {}
+unsafe impl<T: Send, I: IrqControl> Sync for IrqSafeMutex<T, I> {}
+
+impl<T, I: IrqControl> Deref for IrqSafeMutexGuard<'_, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, I: IrqControl> DerefMut for IrqSafeMutexGuard<'_, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// An [`McsMutex`] that masks interrupts while it is held.
+///
+/// The interrupt state is saved and interrupts are masked before trying to
+/// acquire the lock. The saved state is restored after the lock is released.
+pub struct IrqSafeMcsMutex<T, I: IrqControl> {
+    /// Underlying mutex.
+    mutex: McsMutex<T>,
+
+    /// Interrupt control.
+    _irq: PhantomData<I>,
+}
+
+/// An RAII implementation of a "scoped lock" on an interrupt-safe MCS mutex.
+/// When this structure is dropped, the lock will be unlocked and the
+/// interrupt state will be restored.
+///
+/// This structure is created by the [`IrqSafeMcsMutex::lock`] method.
+pub struct IrqSafeMcsMutexGuard<'a, T, I: IrqControl> {
+    /// Guard of the underlying mutex.
+    ///
+    /// Fields are dropped in declaration order, so the lock is released
+    /// before the interrupt state is restored.
+    guard: McsMutexGuard<'a, T>,
+
+    /// Interrupt state saved on lock.
+    _saved: SavedIrqState<I>,
+}
+
+impl<T, I: IrqControl> IrqSafeMcsMutex<T, I> {
+    /// Returns a new [`IrqSafeMcsMutex`] protecting `data`.
+    pub const fn new(data: T) -> IrqSafeMcsMutex<T, I> {
+        IrqSafeMcsMutex {
+            mutex: McsMutex::new(data),
+            _irq: PhantomData,
+        }
+    }
+
+    /// Masks interrupts, locks the mutex and returns an
+    /// [`IrqSafeMcsMutexGuard`] that grants exclusive access to the
+    /// protected data until it is dropped.
+    ///
+    /// `node` is appended to the queue of waiters and it is borrowed until
+    /// the lock is released.
+    ///
+    /// # Safety
+    ///
+    /// The returned guard must be dropped before `node` is moved or freed.
+    /// See [`McsMutex::lock`].
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub unsafe fn lock<'a>(
+        &'a self,
+        node: &'a mut McsNode,
+    ) -> IrqSafeMcsMutexGuard<'a, T, I> {
+        let saved = SavedIrqState::save_and_mask();
+        IrqSafeMcsMutexGuard {
+            guard: unsafe { self.mutex.lock(node) },
+            _saved: saved,
+        }
+    }
+
+    /// Adds the mutex to the registry of locks with the provided `name`. See
+    /// [`debug::locks`](crate::debug::locks).
+    #[cfg(feature = "debug")]
+    pub fn register(&'static self, name: &'static str) {
+        self.mutex.register(name);
+    }
+}
+
+unsafe impl<T: Send, I: IrqControl> Send for IrqSafeMcsMutex<T, I> {}
+unsafe impl<T: Send, I: IrqControl> Sync for IrqSafeMcsMutex<T, I> {}
+
+impl<T, I: IrqControl> Deref for IrqSafeMcsMutexGuard<'_, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, I: IrqControl> DerefMut for IrqSafeMcsMutexGuard<'_, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// A [`RwLock`] that masks interrupts while it is held.
+///
+/// The interrupt state is saved and interrupts are masked before trying to
+/// acquire the lock. The saved state is restored after the lock is released.
+pub struct IrqSafeRwLock<T, I: IrqControl> {
+    /// Underlying lock.
+    lock: RwLock<T>,
+
+    /// Interrupt control.
+    _irq: PhantomData<I>,
+}
+
+/// An RAII implementation of a "scoped shared read lock" on an
+/// interrupt-safe [`RwLock`]. When this structure is dropped, the shared
+/// access will be released and the interrupt state will be restored.
+///
+/// This structure is created by the [`IrqSafeRwLock::read`] method.
+pub struct IrqSafeRwLockReadGuard<'a, T, I: IrqControl> {
+    /// Guard of the underlying lock.
+    ///
+    /// Fields are dropped in declaration order, so the lock is released
+    /// before the interrupt state is restored.
+    guard: RwLockReadGuard<'a, T>,
+
+    /// Interrupt state saved on lock.
+    _saved: SavedIrqState<I>,
+}
+
+/// An RAII implementation of a "scoped exclusive write lock" on an
+/// interrupt-safe [`RwLock`]. When this structure is dropped, the lock will
+/// be unlocked and the interrupt state will be restored.
+///
+/// This structure is created by the [`IrqSafeRwLock::write`] method.
+pub struct IrqSafeRwLockWriteGuard<'a, T, I: IrqControl> {
+    /// Guard of the underlying lock.
+    ///
+    /// Fields are dropped in declaration order, so the lock is released
+    /// before the interrupt state is restored.
+    guard: RwLockWriteGuard<'a, T>,
+
+    /// Interrupt state saved on lock.
+    _saved: SavedIrqState<I>,
+}
+
+impl<T, I: IrqControl> IrqSafeRwLock<T, I> {
+    /// Returns a new [`IrqSafeRwLock`] protecting `data`.
+    pub const fn new(data: T) -> IrqSafeRwLock<T, I> {
+        IrqSafeRwLock {
+            lock: RwLock::new(data),
+            _irq: PhantomData,
+        }
+    }
+
+    /// Masks interrupts, locks the [`IrqSafeRwLock`] with shared read access
+    /// and returns an [`IrqSafeRwLockReadGuard`].
+    pub fn read(&self) -> IrqSafeRwLockReadGuard<'_, T, I> {
+        let saved = SavedIrqState::save_and_mask();
+        IrqSafeRwLockReadGuard {
+            guard: self.lock.read(),
+            _saved: saved,
+        }
+    }
+
+    /// Masks interrupts, locks the [`IrqSafeRwLock`] with exclusive write
+    /// access and returns an [`IrqSafeRwLockWriteGuard`].
+    pub fn write(&self) -> IrqSafeRwLockWriteGuard<'_, T, I> {
+        let saved = SavedIrqState::save_and_mask();
+        IrqSafeRwLockWriteGuard {
+            guard: self.lock.write(),
+            _saved: saved,
+        }
+    }
+}
+
+unsafe impl<T: Send, I: IrqControl> Send for IrqSafeRwLock<T, I> {}
+unsafe impl<T: Send + Sync, I: IrqControl> Sync for IrqSafeRwLock<T, I> {}
+
+impl<T, I: IrqControl> Deref for IrqSafeRwLockReadGuard<'_, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, I: IrqControl> Deref for IrqSafeRwLockWriteGuard<'_, T, I> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, I: IrqControl> DerefMut for IrqSafeRwLockWriteGuard<'_, T, I> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::cell::Cell;
+
+    std::thread_local! {
+        /// Interrupt mask of the current thread.
+        static MASKED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Interrupt control that tracks the interrupt mask of the current
+    /// thread.
+    struct TestIrq;
+
+    unsafe impl IrqControl for TestIrq {
+        type State = bool;
+
+        fn save_and_mask() -> bool {
+            MASKED.with(|masked| masked.replace(true))
+        }
+
+        fn restore(state: bool) {
+            MASKED.with(|masked| masked.set(state))
+        }
+    }
+
+    fn masked() -> bool {
+        MASKED.with(|masked| masked.get())
+    }
+
+    #[test]
+    fn irq_safe_mutex_lock() {
+        let mutex = IrqSafeMutex::<_, TestIrq>::new(0);
+
+        let mut x = mutex.lock();
+        assert!(masked());
+        *x += 1;
+        drop(x);
+        assert!(!masked());
+
+        let x = mutex.lock();
+        assert_eq!(*x, 1);
+    }
+
+    #[test]
+    fn irq_safe_mutex_lock_nested() {
+        let a = IrqSafeMutex::<_, TestIrq>::new(0);
+        let b = IrqSafeMutex::<_, TestIrq>::new(0);
+
+        let x = a.lock();
+        let y = b.lock();
+        drop(y);
+        assert!(masked());
+        drop(x);
+        assert!(!masked());
+    }
+
+    #[test]
+    fn irq_safe_mcs_mutex_lock() {
+        let mutex = IrqSafeMcsMutex::<_, TestIrq>::new(0);
+
+        let mut node = McsNode::new();
+        let mut x = unsafe { mutex.lock(&mut node) };
+        assert!(masked());
+        *x += 1;
+        drop(x);
+        assert!(!masked());
+
+        let x = unsafe { mutex.lock(&mut node) };
+        assert_eq!(*x, 1);
+    }
+
+    #[test]
+    fn irq_safe_rwlock_read_write() {
+        let lock = IrqSafeRwLock::<_, TestIrq>::new(0);
+
+        let mut x = lock.write();
+        assert!(masked());
+        *x += 1;
+        drop(x);
+        assert!(!masked());
+
+        let x = lock.read();
+        assert!(masked());
+        assert_eq!(*x, 1);
+        drop(x);
+        assert!(!masked());
+    }
+}
@@ -4,6 +4,57 @@
 //! [Algorithms for Scalable Synchronization on Shared Memory
 //! Multiprocessors][1].
 //!
+//! # Choosing a mutex
+//!
+//! Both [`TicketMutex`] and [`McsMutex`] grant the lock in FIFO order, so
+//! neither of them can starve a processor. They differ in where the waiters
+//! spin:
+//!
+//! - [`TicketMutex`]: all the waiters spin on the same `now_serving` counter.
+//!   Every release invalidates the cache line in all the waiting processors,
+//!   which generates traffic proportional to the number of waiters. It is the
+//!   simplest and fastest option when contention is low.
+//! - [`McsMutex`]: every waiter spins on its own [`McsNode`]. A release only
+//!   touches the node of the next waiter, so the traffic per release is
+//!   constant regardless of the number of waiters. The price is an extra
+//!   atomic operation on release and the need to provide a queue node when
+//!   locking. The node stays in the queue until the guard is dropped, so
+//!   locking is `unsafe`: forgetting the guard would leave a dangling node
+//!   behind.
+//!
+//! Locks that may also be taken from an interrupt handler must mask
+//! interrupts while they are held. Otherwise, an interrupt arriving on the
+//! processor that holds the lock deadlocks. [`IrqSafeMutex`] and
+//! [`IrqSafeMcsMutex`] wrap the mutexes above and mask interrupts using the
+//! [`IrqControl`] implementation provided by the user.
+//!
+//! Read-mostly data can be protected by a [`RwLock`], which allows several
+//! readers to hold the lock at the same time while keeping the FIFO order
+//! between readers and writers.
+//!
+//! Data that is initialized once and only read afterwards does not need a
+//! lock at all. [`Once`] and [`Lazy`] guarantee that the initialization
+//! runs exactly once and give lock-free access to the value after that.
+//!
+//! # Blocking primitives
+//!
+//! [`Semaphore`], [`Condvar`] and [`Event`] allow waiting for a condition
+//! without holding a lock. How the processor waits is defined by a
+//! [`WaitHint`]. The default one, [`Spin`], busy-waits. Architecture specific
+//! implementations can put the processor in a low-power state instead.
+//!
+//! # Queues
+//!
+//! [`SpscQueue`] and [`MpscQueue`] are fixed-capacity lock-free queues that
+//! can be constructed in a `const` context. They are useful to pass data from
+//! interrupt handlers or other cores without taking a lock.
+//!
+//! # Debugging
+//!
+//! The `debug` feature enables owner tracking, recursive lock detection and
+//! contention statistics for [`TicketMutex`] and [`McsMutex`]. See the
+//! [`debug`] module.
+//!
 //! [1]: https://web.mit.edu/6.173/www/currentsemester/readings/R06-scalable-synchronization-1991.pdf
 
 #![no_std]
@@ -11,8 +62,38 @@
 use core::cell::UnsafeCell;
 use core::hint;
 use core::ops::{Deref, DerefMut};
+#[cfg(feature = "debug")]
+use core::panic::Location;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+mod condvar;
+#[cfg(feature = "debug")]
+pub mod debug;
+mod event;
+mod irq;
+mod mcs;
+mod mpsc;
+mod once;
+mod rwlock;
+mod semaphore;
+mod spsc;
+mod wait;
+
+pub use condvar::Condvar;
+pub use event::Event;
+pub use irq::{
+    IrqControl, IrqSafeMcsMutex, IrqSafeMcsMutexGuard, IrqSafeMutex,
+    IrqSafeMutexGuard, IrqSafeRwLock, IrqSafeRwLockReadGuard,
+    IrqSafeRwLockWriteGuard,
+};
+pub use mcs::{McsMutex, McsMutexGuard, McsNode};
+pub use mpsc::MpscQueue;
+pub use once::{Lazy, Once};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::Semaphore;
+pub use spsc::{Consumer, Producer, SpscQueue};
+pub use wait::{Spin, WaitHint};
+
 /// A mutex based on a ticket lock.
 ///
 /// This type of spin lock ensures FIFO service by granting the lock to
@@ -28,6 +109,10 @@ pub struct TicketMutex<T> {
 
     /// Protected data.
     data: UnsafeCell<T>,
+
+    /// Debug information.
+    #[cfg(feature = "debug")]
+    debug: debug::LockDebug,
 }
 
 /// An RAII implementation of a "scoped lock" on a mutex. When this structure
@@ -49,18 +134,96 @@ impl<T> TicketMutex<T> {
             next_ticket: AtomicUsize::new(0),
             now_serving: AtomicUsize::new(0),
             data: UnsafeCell::new(data),
+            #[cfg(feature = "debug")]
+            debug: debug::LockDebug::new(),
         }
     }
 
     /// Locks the mutex and returns a [`TicketMutexGuard`] that grants
     /// exclusive access to the protected data until it is dropped.
+    ///
+    /// With the `debug` feature, it panics if the mutex is already held by
+    /// the current core.
+    #[cfg_attr(feature = "debug", track_caller)]
     pub fn lock(&self) -> TicketMutexGuard<T> {
+        #[cfg(feature = "debug")]
+        self.debug.check_recursion(Location::caller());
+
         let my_ticket = self.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let mut _spins = 0u64;
         while my_ticket != self.now_serving.load(Ordering::SeqCst) {
+            _spins += 1;
             hint::spin_loop()
         }
+
+        #[cfg(feature = "debug")]
+        self.debug.acquired(Location::caller(), _spins);
+
         TicketMutexGuard { mutex: self }
     }
+
+    /// Tries to lock the mutex. If the lock is held by another processor or
+    /// there are other processors waiting for it, [`None`] is returned.
+    /// Otherwise, it returns a [`TicketMutexGuard`].
+    ///
+    /// A ticket is only taken if it can be served immediately, so a failed
+    /// attempt does not affect the other processors.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn try_lock(&self) -> Option<TicketMutexGuard<'_, T>> {
+        let now_serving = self.now_serving.load(Ordering::SeqCst);
+        self.next_ticket
+            .compare_exchange(
+                now_serving,
+                now_serving.wrapping_add(1),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .ok()?;
+
+        #[cfg(feature = "debug")]
+        self.debug.acquired(Location::caller(), 0);
+
+        Some(TicketMutexGuard { mutex: self })
+    }
+
+    /// Tries to lock the mutex until `timeout` expires. If the lock cannot be
+    /// acquired in time, [`None`] is returned. Otherwise, it returns a
+    /// [`TicketMutexGuard`].
+    ///
+    /// `now` returns the current value of a monotonic clock and `timeout` is
+    /// expressed in the units of that clock. The clock is allowed to wrap
+    /// around.
+    ///
+    /// Taking a ticket commits the processor to wait for its turn, so this
+    /// function retries [`TicketMutex::try_lock`] instead. Thus, the lock is
+    /// not granted in FIFO order to processors calling this function.
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub fn lock_timeout<F>(
+        &self,
+        mut now: F,
+        timeout: u64,
+    ) -> Option<TicketMutexGuard<'_, T>>
+    where
+        F: FnMut() -> u64,
+    {
+        let start = now();
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return Some(guard);
+            }
+            if now().wrapping_sub(start) >= timeout {
+                return None;
+            }
+            hint::spin_loop()
+        }
+    }
+
+    /// Adds the mutex to the registry of locks with the provided `name`. See
+    /// [`debug::locks`].
+    #[cfg(feature = "debug")]
+    pub fn register(&'static self, name: &'static str) {
+        self.debug.register(name);
+    }
 }
 
 unsafe impl<T: Send> Send for TicketMutex<T> {}
@@ -82,6 +245,9 @@ impl<T> DerefMut for TicketMutexGuard<'_, T> {
 
 impl<T> Drop for TicketMutexGuard<'_, T> {
     fn drop(&mut self) {
+        #[cfg(feature = "debug")]
+        self.mutex.debug.released();
+
         self.mutex.now_serving.fetch_add(1, Ordering::SeqCst);
     }
 }
@@ -105,4 +271,59 @@ mod tests {
         let x = mutex.lock();
         assert_eq!(*x, 2);
     }
+
+    #[test]
+    fn ticket_mutex_try_lock() {
+        let mutex = TicketMutex::new(0);
+
+        let mut x = mutex.try_lock().unwrap();
+        *x += 1;
+        assert!(mutex.try_lock().is_none());
+        drop(x);
+
+        let x = mutex.try_lock().unwrap();
+        assert_eq!(*x, 1);
+        drop(x);
+
+        // Failed attempts must not take a ticket.
+        let x = mutex.lock();
+        assert_eq!(*x, 1);
+    }
+
+    #[test]
+    fn ticket_mutex_lock_timeout() {
+        let mutex = TicketMutex::new(0);
+
+        let x = mutex.lock();
+        let mut clock = 0;
+        let y = mutex.lock_timeout(
+            || {
+                clock += 1;
+                clock
+            },
+            10,
+        );
+        assert!(y.is_none());
+        assert_eq!(clock, 11);
+        drop(x);
+
+        assert!(mutex.lock_timeout(|| 0, 10).is_some());
+    }
+
+    #[test]
+    fn ticket_mutex_lock_timeout_wrapping_clock() {
+        let mutex = TicketMutex::new(0);
+
+        let _x = mutex.lock();
+        let mut clock = u64::MAX - 2;
+        let y = mutex.lock_timeout(
+            || {
+                clock = clock.wrapping_add(1);
+                clock
+            },
+            5,
+        );
+        assert!(y.is_none());
+        assert_eq!(clock, 3);
+    }
 }
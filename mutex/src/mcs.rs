@@ -0,0 +1,254 @@
+//! MCS queue lock.
+
+use core::cell::UnsafeCell;
+use core::hint;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "debug")]
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+#[cfg(feature = "debug")]
+use crate::debug;
+
+/// A node of the queue of an [`McsMutex`].
+///
+/// Every processor that tries to acquire the lock provides its own node. The
+/// processor spins on its own node until its predecessor in the queue hands
+/// the lock over. The node must not be moved while it is in the queue. It is
+/// borrowed for the lifetime of the [`McsMutexGuard`], which must be dropped
+/// to take the node out of the queue. See [`McsMutex::lock`].
+#[derive(Debug)]
+pub struct McsNode {
+    /// Next node in the queue.
+    next: AtomicPtr<McsNode>,
+
+    /// True while the processor owning the node must wait.
+    locked: AtomicBool,
+}
+
+impl McsNode {
+    /// Returns a new [`McsNode`].
+    pub const fn new() -> McsNode {
+        McsNode {
+            next: AtomicPtr::new(ptr::null_mut()),
+            locked: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for McsNode {
+    fn default() -> Self {
+        McsNode::new()
+    }
+}
+
+/// A mutex based on the MCS list-based queuing lock.
+///
+/// Like a ticket lock, it grants the lock to processors in FIFO order.
+/// However, every processor spins on a flag in its own [`McsNode`] instead of
+/// a location shared by all the waiters. So, releasing the lock only
+/// invalidates the cache line of the next waiter.
+pub struct McsMutex<T> {
+    /// Last node in the queue. It is null if the lock is free.
+    tail: AtomicPtr<McsNode>,
+
+    /// Protected data.
+    data: UnsafeCell<T>,
+
+    /// Debug information.
+    #[cfg(feature = "debug")]
+    debug: debug::LockDebug,
+}
+
+/// An RAII implementation of a "scoped lock" on a mutex. When this structure
+/// is dropped, the lock will be unlocked.
+///
+/// The data protected by the mutex can be accessed through this guard via its
+/// [`Deref`] and [`DerefMut`] implementations.
+///
+/// This structure is created by the [`McsMutex::lock`] method.
+pub struct McsMutexGuard<'a, T> {
+    /// The mutex that created this [`McsMutexGuard`] on lock.
+    mutex: &'a McsMutex<T>,
+
+    /// The queue node used to acquire the lock.
+    node: &'a McsNode,
+}
+
+impl<T> McsMutex<T> {
+    /// Returns a new [`McsMutex`] protecting `data`.
+    pub const fn new(data: T) -> McsMutex<T> {
+        McsMutex {
+            tail: AtomicPtr::new(ptr::null_mut()),
+            data: UnsafeCell::new(data),
+            #[cfg(feature = "debug")]
+            debug: debug::LockDebug::new(),
+        }
+    }
+
+    /// Locks the mutex and returns an [`McsMutexGuard`] that grants exclusive
+    /// access to the protected data until it is dropped.
+    ///
+    /// `node` is appended to the queue of waiters and it is borrowed until
+    /// the lock is released.
+    ///
+    /// With the `debug` feature, it panics if the mutex is already held by
+    /// the current core.
+    ///
+    /// # Safety
+    ///
+    /// The returned guard must be dropped before `node` is moved or freed.
+    /// E.g. it must not be passed to [`mem::forget`]. Otherwise, `node`
+    /// stays in the queue and the next waiter writes through a dangling
+    /// pointer.
+    ///
+    /// [`mem::forget`]: core::mem::forget
+    #[cfg_attr(feature = "debug", track_caller)]
+    pub unsafe fn lock<'a>(
+        &'a self,
+        node: &'a mut McsNode,
+    ) -> McsMutexGuard<'a, T> {
+        #[cfg(feature = "debug")]
+        self.debug.check_recursion(Location::caller());
+
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+        node.locked.store(true, Ordering::Relaxed);
+
+        let node_ptr = node as *mut McsNode;
+        let pred = self.tail.swap(node_ptr, Ordering::AcqRel);
+        let mut _spins = 0u64;
+        if !pred.is_null() {
+            // The lock is held. Link the node behind its predecessor and wait
+            // until the lock is handed over.
+            unsafe { (*pred).next.store(node_ptr, Ordering::Release) };
+            while node.locked.load(Ordering::Acquire) {
+                _spins += 1;
+                hint::spin_loop()
+            }
+        }
+
+        #[cfg(feature = "debug")]
+        self.debug.acquired(Location::caller(), _spins);
+
+        McsMutexGuard { mutex: self, node }
+    }
+
+    /// Adds the mutex to the registry of locks with the provided `name`. See
+    /// [`debug::locks`].
+    #[cfg(feature = "debug")]
+    pub fn register(&'static self, name: &'static str) {
+        self.debug.register(name);
+    }
+}
+
+unsafe impl<T: Send> Send for McsMutex<T> {}
+unsafe impl<T: Send> Sync for McsMutex<T> {}
+
+impl<T> Deref for McsMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for McsMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for McsMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "debug")]
+        self.mutex.debug.released();
+
+        let node_ptr = self.node as *const McsNode as *mut McsNode;
+
+        let mut next = self.node.next.load(Ordering::Acquire);
+        if next.is_null() {
+            // There is no known successor. If the node is still the tail of
+            // the queue, the lock becomes free.
+            if self
+                .mutex
+                .tail
+                .compare_exchange(
+                    node_ptr,
+                    ptr::null_mut(),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return;
+            }
+
+            // A successor is enqueuing itself. Wait until it is linked.
+            loop {
+                next = self.node.next.load(Ordering::Acquire);
+                if !next.is_null() {
+                    break;
+                }
+                hint::spin_loop()
+            }
+        }
+
+        // Hand the lock over to the successor.
+        unsafe { (*next).locked.store(false, Ordering::Release) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn mcs_mutex_lock() {
+        let mutex = McsMutex::new(0);
+
+        let mut node = McsNode::new();
+        let mut x = unsafe { mutex.lock(&mut node) };
+        *x += 1;
+        drop(x);
+
+        let mut x = unsafe { mutex.lock(&mut node) };
+        *x += 1;
+        drop(x);
+
+        let x = unsafe { mutex.lock(&mut node) };
+        assert_eq!(*x, 2);
+    }
+
+    #[test]
+    fn mcs_mutex_lock_contended() {
+        const NTHREADS: usize = 4;
+        const NITERS: usize = 1_000;
+
+        let mutex = Arc::new(McsMutex::new(0));
+
+        let handles = (0..NTHREADS)
+            .map(|_| {
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    for _ in 0..NITERS {
+                        let mut node = McsNode::new();
+                        *unsafe { mutex.lock(&mut node) } += 1;
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut node = McsNode::new();
+        assert_eq!(*unsafe { mutex.lock(&mut node) }, NTHREADS * NITERS);
+    }
+}
@@ -0,0 +1,332 @@
+//! Lock-free multi-producer queue.
+//!
+//! The implementation is based on Dmitry Vyukov's [bounded MPMC queue].
+//!
+//! [bounded MPMC queue]: https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A slot of an [`MpscQueue`].
+struct Slot<T> {
+    /// Sequence number. If it is equal to the position of a push, the slot
+    /// is free. If it is equal to the position of a pop plus one, the slot
+    /// holds a value.
+    seq: AtomicUsize,
+
+    /// The value.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A fixed-capacity lock-free multi-producer queue.
+///
+/// Any number of processors can push and pop elements concurrently through a
+/// shared reference. Although it is meant to have many producers (e.g.
+/// interrupt handlers running on several cores) and a single consumer, it is
+/// also safe to pop from several consumers.
+///
+/// `N` must be a power of two greater than or equal to 2.
+pub struct MpscQueue<T, const N: usize> {
+    /// Ring buffer.
+    slots: [Slot<T>; N],
+
+    /// Position of the next push.
+    enqueue_pos: AtomicUsize,
+
+    /// Position of the next pop.
+    dequeue_pos: AtomicUsize,
+}
+
+impl<T, const N: usize> MpscQueue<T, N> {
+    /// Returns a new empty [`MpscQueue`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `N` is not a power of two or if it is less
+    /// than 2. With a single slot, the sequence number of a full slot would
+    /// match the position of the next push, so the unread value would be
+    /// overwritten.
+    pub const fn new() -> MpscQueue<T, N> {
+        assert!(
+            N >= 2 && N.is_power_of_two(),
+            "capacity must be a power of two greater than or equal to 2"
+        );
+
+        let mut slots = [const {
+            Slot {
+                seq: AtomicUsize::new(0),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }; N];
+
+        let mut i = 0;
+        while i < N {
+            slots[i].seq = AtomicUsize::new(i);
+            i += 1;
+        }
+
+        MpscQueue {
+            slots,
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes `value` at the back of the queue. If the queue is full,
+    /// `value` is returned back.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & (N - 1)];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+
+            if diff == 0 {
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // The slot still holds the value pushed N positions ago.
+                return Err(value);
+            } else {
+                // Another producer took the position.
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops the element at the front of the queue. If the queue is empty,
+    /// [`None`] is returned.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & (N - 1)];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos.wrapping_add(1)) as isize;
+
+            if diff == 0 {
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value =
+                            unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.seq.store(pos.wrapping_add(N), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                // The slot has not been filled yet.
+                return None;
+            } else {
+                // Another consumer took the position.
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the queue. The value may be stale
+    /// if other processors are pushing or popping elements concurrently.
+    pub fn len(&self) -> usize {
+        let dequeue_pos = self.dequeue_pos.load(Ordering::Acquire);
+        let enqueue_pos = self.enqueue_pos.load(Ordering::Acquire);
+        enqueue_pos.wrapping_sub(dequeue_pos).min(N)
+    }
+
+    /// Returns true if the queue is empty. The value may be stale if other
+    /// processors are pushing or popping elements concurrently.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for MpscQueue<T, N> {
+    fn default() -> Self {
+        MpscQueue::new()
+    }
+}
+
+impl<T, const N: usize> Drop for MpscQueue<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+unsafe impl<T: Send, const N: usize> Send for MpscQueue<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for MpscQueue<T, N> {}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn mpsc_queue_push_pop() {
+        let queue = MpscQueue::<_, 4>::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+
+        for i in 0..4 {
+            queue.push(i).unwrap();
+        }
+        assert_eq!(queue.push(4), Err(4));
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(queue.pop(), Some(0));
+        queue.push(4).unwrap();
+
+        for i in 1..5 {
+            assert_eq!(queue.pop(), Some(i));
+        }
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn mpsc_queue_two_slots() {
+        let queue = MpscQueue::<_, 2>::new();
+        queue.push(0).unwrap();
+        queue.push(1).unwrap();
+        assert_eq!(queue.push(2), Err(2));
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop(), Some(0));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be a power of two")]
+    fn mpsc_queue_one_slot() {
+        MpscQueue::<u32, 1>::new();
+    }
+
+    #[test]
+    fn mpsc_queue_drop() {
+        let value = Arc::new(());
+
+        let queue = MpscQueue::<_, 4>::new();
+        queue.push(Arc::clone(&value)).unwrap();
+        queue.push(Arc::clone(&value)).unwrap();
+        assert_eq!(Arc::strong_count(&value), 3);
+
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn mpsc_queue_stress() {
+        const NPRODUCERS: usize = 4;
+        const NITEMS: usize = 10_000;
+
+        static QUEUE: MpscQueue<(usize, usize), 16> = MpscQueue::new();
+
+        let producers = (0..NPRODUCERS)
+            .map(|id| {
+                thread::spawn(move || {
+                    for i in 0..NITEMS {
+                        let mut value = (id, i);
+                        while let Err(v) = QUEUE.push(value) {
+                            value = v;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        // Elements pushed by the same producer must be popped in order.
+        let mut next = [0; NPRODUCERS];
+        let mut received = 0;
+        while received < NPRODUCERS * NITEMS {
+            match QUEUE.pop() {
+                Some((id, i)) => {
+                    assert_eq!(i, next[id]);
+                    next[id] += 1;
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(next.iter().all(|&n| n == NITEMS));
+        assert!(QUEUE.is_empty());
+    }
+
+    #[test]
+    fn mpsc_queue_stress_consumers() {
+        const NTHREADS: usize = 4;
+        const NITEMS: usize = 10_000;
+
+        let queue = Arc::new(MpscQueue::<usize, 8>::new());
+
+        let producers = (0..NTHREADS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    for i in 0..NITEMS {
+                        while queue.push(i).is_err() {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        let consumers = (0..NTHREADS)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    let mut sum = 0;
+                    for _ in 0..NITEMS {
+                        loop {
+                            if let Some(i) = queue.pop() {
+                                sum += i;
+                                break;
+                            }
+                            thread::yield_now();
+                        }
+                    }
+                    sum
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        let sum = consumers
+            .into_iter()
+            .map(|consumer| consumer.join().unwrap())
+            .sum::<usize>();
+
+        assert_eq!(sum, NTHREADS * (NITEMS * (NITEMS - 1) / 2));
+        assert!(queue.is_empty());
+    }
+}
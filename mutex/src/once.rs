@@ -0,0 +1,298 @@
+//! One-time initialization.
+
+use core::cell::{Cell, UnsafeCell};
+use core::convert::Infallible;
+use core::fmt;
+use core::hint;
+use core::mem::{self, MaybeUninit};
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The value has not been initialized.
+const INCOMPLETE: u8 = 0;
+
+/// A processor is initializing the value.
+const RUNNING: u8 = 1;
+
+/// The value has been initialized.
+const COMPLETE: u8 = 2;
+
+/// A value that is initialized exactly once.
+///
+/// The first processor calling [`Once::call_once`] or
+/// [`Once::try_call_once`] runs the initialization function, while the other
+/// processors wait for it to finish. After that, the value can be accessed
+/// without taking any lock.
+///
+/// Calling [`Once::call_once`] on the same [`Once`] from its initialization
+/// function spins forever.
+pub struct Once<T> {
+    /// Initialization state.
+    state: AtomicU8,
+
+    /// The value. It is only initialized if `state` is `COMPLETE`.
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Resets the state of a [`Once`] to `INCOMPLETE` when dropped. It is used
+/// to allow retrying the initialization if it fails or panics.
+struct ResetOnDrop<'a> {
+    /// State to reset.
+    state: &'a AtomicU8,
+}
+
+impl Drop for ResetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.state.store(INCOMPLETE, Ordering::Release);
+    }
+}
+
+impl<T> Once<T> {
+    /// Returns a new uninitialized [`Once`].
+    pub const fn new() -> Once<T> {
+        Once {
+            state: AtomicU8::new(INCOMPLETE),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initializes the value with `f` if it has not been initialized yet and
+    /// returns a reference to it. If another processor is initializing the
+    /// value, it waits for it to finish.
+    pub fn call_once<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        match self.try_call_once(|| Ok::<T, Infallible>(f())) {
+            Ok(value) => value,
+            Err(err) => match err {},
+        }
+    }
+
+    /// Initializes the value with `f` if it has not been initialized yet and
+    /// returns a reference to it. If another processor is initializing the
+    /// value, it waits for it to finish.
+    ///
+    /// If `f` returns an error, the value stays uninitialized and the error
+    /// is returned. The initialization can be retried later.
+    pub fn try_call_once<F, E>(&self, f: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        loop {
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let reset = ResetOnDrop { state: &self.state };
+                    let value = f()?;
+                    unsafe { (*self.data.get()).write(value) };
+                    mem::forget(reset);
+                    self.state.store(COMPLETE, Ordering::Release);
+                    return Ok(unsafe { self.get_unchecked() });
+                }
+                Err(COMPLETE) => return Ok(unsafe { self.get_unchecked() }),
+                Err(_) => {
+                    while self.state.load(Ordering::Acquire) == RUNNING {
+                        hint::spin_loop()
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns a reference to the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.is_completed() {
+            Some(unsafe { self.get_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the value has been initialized.
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Waits until the value is initialized by another processor and returns
+    /// a reference to it.
+    pub fn wait(&self) -> &T {
+        loop {
+            if let Some(value) = self.get() {
+                return value;
+            }
+            hint::spin_loop()
+        }
+    }
+
+    /// Returns a reference to the value.
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    unsafe fn get_unchecked(&self) -> &T {
+        unsafe { (*self.data.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Default for Once<T> {
+    fn default() -> Self {
+        Once::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Once<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("Once").field(value).finish(),
+            None => f.write_str("Once(<uninitialized>)"),
+        }
+    }
+}
+
+impl<T> Drop for Once<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for Once<T> {}
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+
+/// A value that is initialized on first access.
+///
+/// It is built on top of [`Once`], so the initialization function runs
+/// exactly once even if several processors access the value at the same
+/// time.
+pub struct Lazy<T, F = fn() -> T> {
+    /// The value.
+    once: Once<T>,
+
+    /// Initialization function. It is taken by the processor that
+    /// initializes the value.
+    init: Cell<Option<F>>,
+}
+
+impl<T, F> Lazy<T, F> {
+    /// Returns a new [`Lazy`] that will be initialized with `f`.
+    pub const fn new(f: F) -> Lazy<T, F> {
+        Lazy {
+            once: Once::new(),
+            init: Cell::new(Some(f)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Forces the initialization of the value and returns a reference to it.
+    pub fn force(this: &Lazy<T, F>) -> &T {
+        this.once.call_once(|| match this.init.take() {
+            Some(f) => f(),
+            None => panic!("Lazy initialization function panicked"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Lazy::force(self)
+    }
+}
+
+unsafe impl<T: Send, F: Send> Send for Lazy<T, F> {}
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn once_call_once() {
+        let once = Once::new();
+        assert!(once.get().is_none());
+
+        assert_eq!(*once.call_once(|| 1), 1);
+        assert_eq!(*once.call_once(|| 2), 1);
+        assert_eq!(once.get(), Some(&1));
+        assert!(once.is_completed());
+    }
+
+    #[test]
+    fn once_try_call_once_retry() {
+        let once = Once::new();
+
+        assert_eq!(once.try_call_once(|| Err(())), Err(()));
+        assert!(!once.is_completed());
+
+        assert_eq!(once.try_call_once(|| Ok::<_, ()>(1)), Ok(&1));
+        assert_eq!(once.try_call_once(|| Err(())), Ok(&1));
+    }
+
+    #[test]
+    fn once_call_once_contended() {
+        const NTHREADS: usize = 4;
+
+        let once = Arc::new(Once::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..NTHREADS)
+            .map(|i| {
+                let once = Arc::clone(&once);
+                let calls = Arc::clone(&calls);
+                thread::spawn(move || {
+                    *once.call_once(|| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        i
+                    })
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        let values = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<std::vec::Vec<_>>();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(values.iter().all(|&value| value == values[0]));
+    }
+
+    #[test]
+    fn once_drop() {
+        let value = Arc::new(());
+
+        let once = Once::new();
+        once.call_once(|| Arc::clone(&value));
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        drop(once);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn lazy_deref() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static LAZY: Lazy<usize> = Lazy::new(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            42
+        });
+
+        assert_eq!(*LAZY, 42);
+        assert_eq!(*LAZY, 42);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
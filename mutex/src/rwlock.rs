@@ -0,0 +1,200 @@
+//! Fair reader-writer lock.
+
+use core::cell::UnsafeCell;
+use core::hint;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Increment of the request and completion counters for a reader. Readers
+/// are counted in the low 32 bits.
+const READER_INC: u64 = 1;
+
+/// Increment of the request and completion counters for a writer. Writers
+/// are counted in the high 32 bits.
+const WRITER_INC: u64 = 1 << 32;
+
+/// Returns the number of writers of a request or completion counter.
+fn writers(counter: u64) -> u64 {
+    counter >> 32
+}
+
+/// A reader-writer lock based on a fair ticket lock.
+///
+/// The lock is granted in the same order in which it was requested. Several
+/// consecutive readers can hold the lock at the same time, while a writer
+/// waits until all the previous readers and writers are done. Readers
+/// requesting the lock after a writer wait for the writer, so writers
+/// cannot be starved by a continuous stream of readers.
+///
+/// It is useful to protect read-mostly data that is accessed from several
+/// processors.
+pub struct RwLock<T> {
+    /// Number of requests to acquire the lock. Writers are counted in the
+    /// high 32 bits and readers in the low 32 bits.
+    requests: AtomicU64,
+
+    /// Number of times the lock has been released. It uses the same layout
+    /// as `requests`.
+    completions: AtomicU64,
+
+    /// Protected data.
+    data: UnsafeCell<T>,
+}
+
+/// An RAII implementation of a "scoped shared read lock" on a [`RwLock`].
+/// When this structure is dropped, the shared access will be released.
+///
+/// This structure is created by the [`RwLock::read`] method.
+pub struct RwLockReadGuard<'a, T> {
+    /// The lock that created this [`RwLockReadGuard`].
+    lock: &'a RwLock<T>,
+}
+
+/// An RAII implementation of a "scoped exclusive write lock" on a
+/// [`RwLock`]. When this structure is dropped, the lock will be unlocked.
+///
+/// This structure is created by the [`RwLock::write`] method.
+pub struct RwLockWriteGuard<'a, T> {
+    /// The lock that created this [`RwLockWriteGuard`].
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Returns a new [`RwLock`] protecting `data`.
+    pub const fn new(data: T) -> RwLock<T> {
+        RwLock {
+            requests: AtomicU64::new(0),
+            completions: AtomicU64::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Locks the [`RwLock`] with shared read access and returns an
+    /// [`RwLockReadGuard`]. It waits until all the writers that requested
+    /// the lock before are done.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        let prev = self.requests.fetch_add(READER_INC, Ordering::SeqCst);
+        let prev_writers = writers(prev);
+        while writers(self.completions.load(Ordering::SeqCst)) != prev_writers {
+            hint::spin_loop()
+        }
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Locks the [`RwLock`] with exclusive write access and returns an
+    /// [`RwLockWriteGuard`]. It waits until all the readers and writers that
+    /// requested the lock before are done.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        let prev = self.requests.fetch_add(WRITER_INC, Ordering::SeqCst);
+        while self.completions.load(Ordering::SeqCst) != prev {
+            hint::spin_loop()
+        }
+        RwLockWriteGuard { lock: self }
+    }
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .completions
+            .fetch_add(READER_INC, Ordering::SeqCst);
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .completions
+            .fetch_add(WRITER_INC, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn rwlock_read_write() {
+        let lock = RwLock::new(0);
+
+        let mut x = lock.write();
+        *x += 1;
+        drop(x);
+
+        let x = lock.read();
+        let y = lock.read();
+        assert_eq!(*x, 1);
+        assert_eq!(*y, 1);
+        drop(x);
+        drop(y);
+
+        let mut x = lock.write();
+        *x += 1;
+        drop(x);
+
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn rwlock_contended() {
+        const NTHREADS: usize = 4;
+        const NITERS: usize = 1_000;
+
+        let lock = Arc::new(RwLock::new((0, 0)));
+
+        let handles = (0..NTHREADS)
+            .map(|i| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    for _ in 0..NITERS {
+                        if i < NTHREADS / 2 {
+                            let mut x = lock.write();
+                            x.0 += 1;
+                            x.1 += 1;
+                        } else {
+                            let x = lock.read();
+                            assert_eq!(x.0, x.1);
+                        }
+                    }
+                })
+            })
+            .collect::<std::vec::Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let x = lock.read();
+        assert_eq!(x.0, NTHREADS / 2 * NITERS);
+    }
+}
@@ -0,0 +1,105 @@
+//! Counting semaphore.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::wait::{Spin, WaitHint};
+
+/// A counting semaphore.
+///
+/// The semaphore holds a number of permits. [`Semaphore::acquire`] takes a
+/// permit, waiting with `W` while there are none available, and
+/// [`Semaphore::release`] returns it.
+///
+/// Permits are not granted in FIFO order.
+pub struct Semaphore<W: WaitHint = Spin> {
+    /// Number of available permits.
+    permits: AtomicUsize,
+
+    /// Waiting strategy.
+    _wait: PhantomData<W>,
+}
+
+impl<W: WaitHint> Semaphore<W> {
+    /// Returns a new [`Semaphore`] with `permits` available permits.
+    pub const fn new(permits: usize) -> Semaphore<W> {
+        Semaphore {
+            permits: AtomicUsize::new(permits),
+            _wait: PhantomData,
+        }
+    }
+
+    /// Takes a permit, waiting until one is available.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            W::wait()
+        }
+    }
+
+    /// Tries to take a permit. It returns false if there are no available
+    /// permits.
+    pub fn try_acquire(&self) -> bool {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |permits| {
+                permits.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Returns a permit and wakes the waiting processors.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        W::wake();
+    }
+
+    /// Returns the number of available permits.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn semaphore_try_acquire() {
+        let sem = Semaphore::<Spin>::new(2);
+
+        assert!(sem.try_acquire());
+        assert!(sem.try_acquire());
+        assert!(!sem.try_acquire());
+
+        sem.release();
+        assert_eq!(sem.available_permits(), 1);
+        assert!(sem.try_acquire());
+    }
+
+    #[test]
+    fn semaphore_acquire_release() {
+        const NITERS: usize = 1_000;
+
+        let sem = Arc::new(Semaphore::<Spin>::new(0));
+
+        let producer = {
+            let sem = Arc::clone(&sem);
+            thread::spawn(move || {
+                for _ in 0..NITERS {
+                    sem.release();
+                }
+            })
+        };
+
+        for _ in 0..NITERS {
+            sem.acquire();
+        }
+
+        producer.join().unwrap();
+        assert_eq!(sem.available_permits(), 0);
+    }
+}
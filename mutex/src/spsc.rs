@@ -0,0 +1,291 @@
+//! Lock-free single-producer single-consumer queue.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A fixed-capacity lock-free single-producer single-consumer queue.
+///
+/// Elements are pushed through a [`Producer`] and popped through a
+/// [`Consumer`]. At most one handle of each kind can exist at a time, which
+/// is checked when the handle is requested. So, an interrupt handler can
+/// request a [`Producer`] every time it runs, as long as the interrupted
+/// code is not pushing elements into the same queue.
+///
+/// `N` must be a power of two.
+pub struct SpscQueue<T, const N: usize> {
+    /// Ring buffer.
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+
+    /// Number of elements popped. It is only written by the consumer.
+    head: AtomicUsize,
+
+    /// Number of elements pushed. It is only written by the producer.
+    tail: AtomicUsize,
+
+    /// True while a [`Producer`] exists.
+    producer_taken: AtomicBool,
+
+    /// True while a [`Consumer`] exists.
+    consumer_taken: AtomicBool,
+}
+
+/// The producer side of an [`SpscQueue`]. When this structure is dropped,
+/// another producer can be requested.
+///
+/// This structure is created by the [`SpscQueue::producer`] method.
+pub struct Producer<'a, T, const N: usize> {
+    /// The queue that created this [`Producer`].
+    queue: &'a SpscQueue<T, N>,
+}
+
+/// The consumer side of an [`SpscQueue`]. When this structure is dropped,
+/// another consumer can be requested.
+///
+/// This structure is created by the [`SpscQueue::consumer`] method.
+pub struct Consumer<'a, T, const N: usize> {
+    /// The queue that created this [`Consumer`].
+    queue: &'a SpscQueue<T, N>,
+}
+
+impl<T, const N: usize> SpscQueue<T, N> {
+    /// Returns a new empty [`SpscQueue`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `N` is not a power of two.
+    pub const fn new() -> SpscQueue<T, N> {
+        assert!(N.is_power_of_two(), "capacity must be a power of two");
+
+        SpscQueue {
+            buffer: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            producer_taken: AtomicBool::new(false),
+            consumer_taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the [`Producer`] of the queue. If there is already a
+    /// [`Producer`], [`None`] is returned.
+    pub fn producer(&self) -> Option<Producer<'_, T, N>> {
+        if self.producer_taken.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        Some(Producer { queue: self })
+    }
+
+    /// Returns the [`Consumer`] of the queue. If there is already a
+    /// [`Consumer`], [`None`] is returned.
+    pub fn consumer(&self) -> Option<Consumer<'_, T, N>> {
+        if self.consumer_taken.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        Some(Consumer { queue: self })
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    /// Returns true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the slot corresponding to the position `pos`.
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.buffer[pos & (N - 1)].get()
+    }
+}
+
+impl<T, const N: usize> Default for SpscQueue<T, N> {
+    fn default() -> Self {
+        SpscQueue::new()
+    }
+}
+
+impl<T, const N: usize> Drop for SpscQueue<T, N> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut pos = head;
+        while pos != tail {
+            unsafe { (*self.slot(pos)).assume_init_drop() };
+            pos = pos.wrapping_add(1);
+        }
+    }
+}
+
+unsafe impl<T: Send, const N: usize> Send for SpscQueue<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for SpscQueue<T, N> {}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Pushes `value` at the back of the queue. If the queue is full,
+    /// `value` is returned back.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let queue = self.queue;
+
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == N {
+            return Err(value);
+        }
+
+        unsafe { (*queue.slot(tail)).write(value) };
+        queue.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Returns true if the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.queue.len() == N
+    }
+}
+
+impl<T, const N: usize> Drop for Producer<'_, T, N> {
+    fn drop(&mut self) {
+        self.queue.producer_taken.store(false, Ordering::Release);
+    }
+}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Pops the element at the front of the queue. If the queue is empty,
+    /// [`None`] is returned.
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = self.queue;
+
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let value = unsafe { (*queue.slot(head)).assume_init_read() };
+        queue.head.store(head.wrapping_add(1), Ordering::Release);
+
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Iterator for Consumer<'_, T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop()
+    }
+}
+
+impl<T, const N: usize> Drop for Consumer<'_, T, N> {
+    fn drop(&mut self) {
+        self.queue.consumer_taken.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn spsc_queue_push_pop() {
+        let queue = SpscQueue::<_, 4>::new();
+        let mut producer = queue.producer().unwrap();
+        let mut consumer = queue.consumer().unwrap();
+
+        assert!(queue.is_empty());
+        assert_eq!(consumer.pop(), None);
+
+        for i in 0..4 {
+            producer.push(i).unwrap();
+        }
+        assert!(producer.is_full());
+        assert_eq!(producer.push(4), Err(4));
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(consumer.pop(), Some(0));
+        producer.push(4).unwrap();
+
+        assert_eq!(
+            consumer.by_ref().collect::<std::vec::Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn spsc_queue_handles() {
+        let queue = SpscQueue::<u8, 2>::new();
+
+        let producer = queue.producer().unwrap();
+        assert!(queue.producer().is_none());
+        drop(producer);
+        assert!(queue.producer().is_some());
+
+        let consumer = queue.consumer().unwrap();
+        assert!(queue.consumer().is_none());
+        drop(consumer);
+        assert!(queue.consumer().is_some());
+    }
+
+    #[test]
+    fn spsc_queue_drop() {
+        let value = Arc::new(());
+
+        let queue = SpscQueue::<_, 4>::new();
+        let mut producer = queue.producer().unwrap();
+        producer.push(Arc::clone(&value)).unwrap();
+        producer.push(Arc::clone(&value)).unwrap();
+        drop(producer);
+        assert_eq!(Arc::strong_count(&value), 3);
+
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn spsc_queue_stress() {
+        const NITEMS: usize = 100_000;
+
+        static QUEUE: SpscQueue<usize, 16> = SpscQueue::new();
+
+        let producer = thread::spawn(|| {
+            let mut producer = QUEUE.producer().unwrap();
+            for i in 0..NITEMS {
+                let mut value = i;
+                while let Err(v) = producer.push(value) {
+                    value = v;
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut consumer = QUEUE.consumer().unwrap();
+        let mut expected = 0;
+        while expected < NITEMS {
+            match consumer.pop() {
+                Some(value) => {
+                    assert_eq!(value, expected);
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+
+        producer.join().unwrap();
+        assert!(QUEUE.is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+//! Waiting strategies used by the blocking primitives.
+
+use core::hint;
+
+/// Tells the processor how to wait for a condition to change and how to
+/// wake the processors waiting for it.
+///
+/// The blocking primitives of this crate ([`Semaphore`](crate::Semaphore),
+/// [`Condvar`](crate::Condvar) and [`Event`](crate::Event)) check their
+/// condition, call [`WaitHint::wait`] while it does not hold and call
+/// [`WaitHint::wake`] after changing it. Because the condition is always
+/// checked again, [`WaitHint::wait`] is allowed to return spuriously.
+///
+/// For instance, on AArch64, [`WaitHint::wait`] can execute `wfe` to put the
+/// processor in a low-power state and [`WaitHint::wake`] can execute `sev` to
+/// wake the processors waiting in `wfe`.
+pub trait WaitHint {
+    /// Waits until the condition may have changed.
+    fn wait();
+
+    /// Wakes the processors waiting in [`WaitHint::wait`].
+    fn wake();
+}
+
+/// Waiting strategy that busy-waits with [`hint::spin_loop`].
+///
+/// It works everywhere, so it is the default strategy.
+#[derive(Debug)]
+pub struct Spin;
+
+impl WaitHint for Spin {
+    fn wait() {
+        hint::spin_loop()
+    }
+
+    fn wake() {}
+}
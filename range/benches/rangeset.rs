@@ -0,0 +1,154 @@
+//! Compares the performance of `RangeSet` and `TreeRangeSet` when they are
+//! used to track the free memory of a fragmented heap, both with small
+//! allocations and with page allocations, whose alignment is their size.
+//!
+//! Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use range::{FixedRangeSet, Range, TreeRangeSet};
+
+/// Maximum number of ranges in the sets.
+const CAPACITY: usize = 8192;
+
+/// Number of live allocations after the heap has been fragmented.
+const LIVE: usize = 4096;
+
+/// Number of allocations and deallocations that are measured.
+const ITERATIONS: usize = 100_000;
+
+/// Size and alignment of the page allocations.
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Common interface of the benchmarked sets.
+trait FreeMemory {
+    fn insert(&mut self, range: Range);
+    fn remove(&mut self, range: Range);
+    fn first_fit(&self, size: u64, align: u64) -> Option<Range>;
+}
+
+impl FreeMemory for FixedRangeSet<CAPACITY> {
+    fn insert(&mut self, range: Range) {
+        FixedRangeSet::insert(self, range).unwrap();
+    }
+
+    fn remove(&mut self, range: Range) {
+        FixedRangeSet::remove(self, range).unwrap();
+    }
+
+    fn first_fit(&self, size: u64, align: u64) -> Option<Range> {
+        FixedRangeSet::first_fit(self, size, align)
+    }
+}
+
+impl FreeMemory for TreeRangeSet<CAPACITY> {
+    fn insert(&mut self, range: Range) {
+        TreeRangeSet::insert(self, range).unwrap();
+    }
+
+    fn remove(&mut self, range: Range) {
+        TreeRangeSet::remove(self, range).unwrap();
+    }
+
+    fn first_fit(&self, size: u64, align: u64) -> Option<Range> {
+        TreeRangeSet::first_fit(self, size, align)
+    }
+}
+
+/// xorshift64 pseudo-random number generator.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a random allocation size and alignment.
+    fn layout(&mut self) -> (u64, u64) {
+        let size = 16 + self.next() % 4096;
+        let align = 8 << (self.next() % 4);
+        (size, align)
+    }
+}
+
+/// Allocates a block from `free`.
+fn alloc(free: &mut impl FreeMemory, size: u64, align: u64) -> Range {
+    let block = free.first_fit(size, align).expect("out of memory");
+    free.remove(block);
+    block
+}
+
+/// Fragments the heap and, then, measures the time needed to perform
+/// `ITERATIONS` pairs of random deallocations and allocations.
+fn run(free: &mut impl FreeMemory) -> Duration {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    free.insert(Range::new(0, (1 << 32) - 1).unwrap());
+
+    // Fragment the heap by allocating twice the number of live blocks and
+    // freeing every other one.
+    let blocks = (0..2 * LIVE)
+        .map(|_| {
+            let (size, align) = rng.layout();
+            alloc(free, size, align)
+        })
+        .collect::<Vec<_>>();
+    let mut live = blocks
+        .chunks(2)
+        .map(|pair| {
+            free.insert(pair[1]);
+            pair[0]
+        })
+        .collect::<Vec<_>>();
+
+    let now = Instant::now();
+    for _ in 0..ITERATIONS {
+        let idx = rng.next() as usize % live.len();
+        free.insert(live.swap_remove(idx));
+
+        let (size, align) = rng.layout();
+        live.push(alloc(free, size, align));
+    }
+    black_box(&free);
+    now.elapsed()
+}
+
+/// Fragments the heap with free ranges of `PAGE_SIZE` points that cannot
+/// hold an aligned page and, then, measures the time needed to perform
+/// `ITERATIONS` pairs of page allocations and deallocations.
+fn run_aligned(free: &mut impl FreeMemory) -> Duration {
+    for i in 0..LIVE as u64 {
+        let start = 2 * i * PAGE_SIZE + 1;
+        free.insert(Range::from_start_size(start, PAGE_SIZE).unwrap());
+    }
+    free.insert(Range::new(1 << 32, (1 << 33) - 1).unwrap());
+
+    let now = Instant::now();
+    for _ in 0..ITERATIONS {
+        let page = alloc(free, PAGE_SIZE, PAGE_SIZE);
+        free.insert(page);
+    }
+    black_box(&free);
+    now.elapsed()
+}
+
+fn main() {
+    let mut rangeset = Box::new(FixedRangeSet::<CAPACITY>::default());
+    let elapsed = run(rangeset.as_mut());
+    println!("RangeSet:     {elapsed:?}");
+
+    let mut treerangeset = Box::new(TreeRangeSet::<CAPACITY>::new());
+    let elapsed = run(treerangeset.as_mut());
+    println!("TreeRangeSet: {elapsed:?}");
+
+    let mut rangeset = Box::new(FixedRangeSet::<CAPACITY>::default());
+    let elapsed = run_aligned(rangeset.as_mut());
+    println!("RangeSet (pages):     {elapsed:?}");
+
+    let mut treerangeset = Box::new(TreeRangeSet::<CAPACITY>::new());
+    let elapsed = run_aligned(treerangeset.as_mut());
+    println!("TreeRangeSet (pages): {elapsed:?}");
+}
@@ -1,11 +1,36 @@
 //! Library for working with sets of overlapping ranges.
+//!
+//! The ranges of a [`RangeSet`] are kept in a [`Storage`]. By default, it is
+//! a fixed-size array, which does not need an allocator. With the `alloc`
+//! feature, a `Vec` can be used instead.
+//!
+//! Most operations on a [`RangeSet`] take linear time. A [`TreeRangeSet`]
+//! keeps the ranges in a balanced tree instead, so it scales better with
+//! the number of ranges.
+//!
+//! A [`RangeMap`] associates values with non-overlapping ranges. For
+//! instance, it can be used to keep track of what owns every region of the
+//! physical memory.
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use core::cmp::max;
 use core::fmt;
+use core::ops;
+use core::slice;
+
+mod map;
+mod tree;
+
+pub use map::{RangeMap, RangeMapIter, RANGE_MAP_SIZE};
+pub use tree::{TreeRangeSet, TreeRangeSetIter};
 
-/// Represents an error related to a `Range` or a `RangeSet`.
+/// Represents an error related to a `Range`, a `RangeSet` or a `RangeMap`.
 #[derive(Debug)]
 pub enum Error {
     /// Invalid range boundaries.
@@ -14,6 +39,16 @@ pub enum Error {
     /// The fixed size array that backs the `RangeSet` is full. It is not
     /// possible to add more ranges.
     FullRangeSet,
+
+    /// The fixed size array that backs the `RangeMap` is full. It is not
+    /// possible to add more ranges.
+    FullRangeMap,
+
+    /// The range does not contain any point.
+    EmptyRange,
+
+    /// The end point of the range does not fit in a `u64`.
+    Overflow(u64, u64),
 }
 
 impl fmt::Display for Error {
@@ -25,6 +60,13 @@ impl fmt::Display for Error {
             Error::FullRangeSet => {
                 write!(f, "RangeSet internal buffer is full")
             }
+            Error::FullRangeMap => {
+                write!(f, "RangeMap internal buffer is full")
+            }
+            Error::EmptyRange => write!(f, "empty range"),
+            Error::Overflow(start, size) => {
+                write!(f, "range overflow: start={start:#x} size={size:#x}")
+            }
         }
     }
 }
@@ -54,6 +96,21 @@ impl Range {
         }
     }
 
+    /// Returns a new `Range` of `size` points starting at `start`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::EmptyRange` if `size` is zero and
+    /// `Error::Overflow` if the end point of the range does not fit in a
+    /// `u64`.
+    pub fn from_start_size(start: u64, size: u64) -> Result<Self, Error> {
+        let last = size.checked_sub(1).ok_or(Error::EmptyRange)?;
+        let end = start
+            .checked_add(last)
+            .ok_or(Error::Overflow(start, size))?;
+        Ok(Range { start, end })
+    }
+
     /// Returns the start point of the range.
     pub fn start(&self) -> u64 {
         self.start
@@ -88,86 +145,280 @@ impl Range {
     }
 }
 
-/// Size of the fixed-size array that contains the `RangeSet` entries.
-const RANGE_SET_SIZE: usize = 256;
+impl From<Range> for ops::RangeInclusive<u64> {
+    fn from(range: Range) -> Self {
+        range.start..=range.end
+    }
+}
 
-/// Represents a set of ranges.
-#[derive(Debug)]
-pub struct RangeSet {
-    /// Ranges within the `RangeSet`.
-    ranges: [Range; RANGE_SET_SIZE],
+/// Implements the conversions from the ranges of `core::ops` with the
+/// provided bound types.
+macro_rules! impl_try_from_ops {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<ops::Range<$ty>> for Range {
+                type Error = Error;
+
+                /// Converts a half-open range into a `Range`.
+                ///
+                /// # Errors
+                ///
+                /// This function returns `Error::EmptyRange` if the range
+                /// does not contain any point.
+                fn try_from(range: ops::Range<$ty>) -> Result<Self, Error> {
+                    if range.is_empty() {
+                        return Err(Error::EmptyRange);
+                    }
+                    Ok(Range {
+                        start: range.start as u64,
+                        end: range.end as u64 - 1,
+                    })
+                }
+            }
+
+            impl TryFrom<ops::RangeInclusive<$ty>> for Range {
+                type Error = Error;
+
+                /// Converts an inclusive range into a `Range`.
+                ///
+                /// # Errors
+                ///
+                /// This function returns `Error::InvalidBoundaries` if the
+                /// end point is lower than the start point of the range and
+                /// `Error::EmptyRange` if the range has been exhausted by
+                /// iterating over it.
+                fn try_from(
+                    range: ops::RangeInclusive<$ty>,
+                ) -> Result<Self, Error> {
+                    let exhausted = range.is_empty();
+                    let range =
+                        Range::new(*range.start() as u64, *range.end() as u64)?;
+                    if exhausted {
+                        return Err(Error::EmptyRange);
+                    }
+                    Ok(range)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_ops!(u32, u64, usize);
+
+/// Returns the lowest range of `size` points contained by `range` whose start
+/// point is aligned to `align`, which must be a power of two.
+fn fit(range: Range, size: u64, align: u64) -> Option<Range> {
+    let start = range.start.checked_add(align - 1)? & !(align - 1);
+    let fit = Range::from_start_size(start, size).ok()?;
+    range.contains_range(fit).then_some(fit)
+}
+
+/// Backing storage of a [`RangeSet`].
+///
+/// It holds the ranges of the set sorted by their start point. The
+/// [`RangeSet`] is in charge of keeping them sorted and merged, so the
+/// storage only needs to insert and remove ranges at a given position.
+pub trait Storage {
+    /// Returns the ranges in the storage.
+    fn as_slice(&self) -> &[Range];
+
+    /// Returns the ranges in the storage as a mutable slice.
+    fn as_mut_slice(&mut self) -> &mut [Range];
+
+    /// Inserts `range` at position `idx`, shifting all the ranges after it
+    /// forward.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeSet` if there is no space for
+    /// the new range.
+    fn insert(&mut self, idx: usize, range: Range) -> Result<(), Error>;
+
+    /// Removes the range at position `idx`, shifting all the ranges after it
+    /// backward.
+    fn remove(&mut self, idx: usize);
+
+    /// Returns the number of ranges in the storage.
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Returns `true` if the storage does not contain any range.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Fixed-size array that can hold up to `N` ranges.
+///
+/// It does not require an allocator, so it can be used to bootstrap one.
+#[derive(Debug, Copy, Clone)]
+pub struct ArrayStorage<const N: usize> {
+    /// Fixed-size array.
+    ranges: [Range; N],
 
     /// Number of elements in the fixed size array that are being used.
     in_use: usize,
 }
 
-impl Default for RangeSet {
+impl<const N: usize> ArrayStorage<N> {
+    /// Returns an empty `ArrayStorage`.
+    pub const fn new() -> Self {
+        ArrayStorage {
+            ranges: [Range { start: 0, end: 0 }; N],
+            in_use: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for ArrayStorage<N> {
     fn default() -> Self {
-        RangeSet::new()
+        ArrayStorage::new()
+    }
+}
+
+impl<const N: usize> Storage for ArrayStorage<N> {
+    fn as_slice(&self) -> &[Range] {
+        &self.ranges[..self.in_use]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Range] {
+        &mut self.ranges[..self.in_use]
+    }
+
+    fn insert(&mut self, idx: usize, range: Range) -> Result<(), Error> {
+        // There must be space at least for the new range.
+        if self.in_use >= N {
+            return Err(Error::FullRangeSet);
+        }
+
+        // Create space for the new range, moving the existing ones forward one
+        // position.
+        self.ranges.copy_within(idx..self.in_use, idx + 1);
+        self.ranges[idx] = range;
+        self.in_use += 1;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: usize) {
+        self.ranges.copy_within(idx + 1..self.in_use, idx);
+        self.in_use -= 1;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Storage for Vec<Range> {
+    fn as_slice(&self) -> &[Range] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Range] {
+        self
+    }
+
+    fn insert(&mut self, idx: usize, range: Range) -> Result<(), Error> {
+        self.try_reserve(1).map_err(|_| Error::FullRangeSet)?;
+        Vec::insert(self, idx, range);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: usize) {
+        Vec::remove(self, idx);
+    }
+}
+
+/// Size of the fixed-size array that contains the entries of the default
+/// `RangeSet`.
+pub const RANGE_SET_SIZE: usize = 256;
+
+/// Represents a set of ranges.
+///
+/// The ranges are kept in a [`Storage`]. By default, it is an
+/// [`ArrayStorage`] that can hold up to [`RANGE_SET_SIZE`] ranges.
+#[derive(Debug)]
+pub struct RangeSet<S = ArrayStorage<RANGE_SET_SIZE>> {
+    /// Ranges within the `RangeSet`.
+    storage: S,
+}
+
+/// `RangeSet` backed by a fixed-size array of `N` ranges.
+pub type FixedRangeSet<const N: usize> = RangeSet<ArrayStorage<N>>;
+
+/// `RangeSet` backed by a [`Vec`], whose capacity is only limited by the
+/// available memory.
+#[cfg(feature = "alloc")]
+pub type VecRangeSet = RangeSet<Vec<Range>>;
+
+impl<S: Storage + Default> Default for RangeSet<S> {
+    fn default() -> Self {
+        RangeSet::with_storage(S::default())
     }
 }
 
 impl RangeSet {
-    /// Returns an empty `RangeSet`.
+    /// Returns an empty `RangeSet` that can hold up to [`RANGE_SET_SIZE`]
+    /// ranges.
     pub fn new() -> Self {
-        RangeSet {
-            ranges: [Range::default(); RANGE_SET_SIZE],
-            in_use: 0,
-        }
+        RangeSet::with_storage(ArrayStorage::new())
+    }
+}
+
+impl<S: Storage> RangeSet<S> {
+    /// Returns a `RangeSet` backed by `storage`, which must be empty.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `storage` is not empty.
+    pub fn with_storage(storage: S) -> Self {
+        assert!(storage.is_empty(), "storage is not empty");
+        RangeSet { storage }
     }
 
     /// Returns the ranges in the `RangeSet`.
     pub fn ranges(&self) -> &[Range] {
-        &self.ranges[..self.in_use]
+        self.storage.as_slice()
     }
 
-    /// Inserts a range into the internal `ranges` array preserving the order
-    /// of the array and avoiding duplicated start points.
+    /// Inserts a range into the internal storage preserving the order of the
+    /// ranges and avoiding duplicated start points.
     fn sort_insert(&mut self, range: Range) -> Result<(), Error> {
+        let ranges = self.storage.as_mut_slice();
+
         // Find the index of the new range.
-        let mut idx = self.in_use;
-        for i in 0..self.in_use {
+        let mut idx = ranges.len();
+        for (i, r) in ranges.iter_mut().enumerate() {
             // If there is a range with the same start point, reuse the same
             // range updating its end point to the greatest value between the
             // new and the old one.
-            if range.start == self.ranges[i].start {
-                self.ranges[i].end = max(range.end, self.ranges[i].end);
+            if range.start == r.start {
+                r.end = max(range.end, r.end);
                 return Ok(());
             }
 
-            if range.start < self.ranges[i].start {
+            if range.start < r.start {
                 idx = i;
                 break;
             }
         }
 
-        // There must be space at least for the new range.
-        if self.in_use >= self.ranges.len() {
-            return Err(Error::FullRangeSet);
-        }
-
-        // Create space for the new range, moving the existing ones forward one
-        // position.
-        self.ranges.copy_within(idx..self.in_use, idx + 1);
-        self.ranges[idx] = range;
-        self.in_use += 1;
-
-        Ok(())
+        self.storage.insert(idx, range)
     }
 
-    /// Merges the overlapping ranges in the internal `ranges` array. It
-    /// assumes that the internal `ranges` array is sorted and there are no
-    /// duplicated start points. Thus, `RangeSet::sort_insert` must be used
-    /// internally to insert new ranges.
+    /// Merges the overlapping ranges in the internal storage. It assumes that
+    /// the ranges are sorted and there are no duplicated start points. Thus,
+    /// `RangeSet::sort_insert` must be used internally to insert new ranges.
     fn merge(&mut self) {
         let mut i = 0;
-        while i < self.in_use - 1 {
+        while i + 1 < self.storage.len() {
+            let ranges = self.storage.as_mut_slice();
+
             // If the ranges are not contiguous or overlapped, continue. We use
             // `saturating_add` because in the following case the ranges must
             // be merged:
             //
             // max] [max => max > max == false => merge
-            if self.ranges[i + 1].start > self.ranges[i].end.saturating_add(1) {
+            if ranges[i + 1].start > ranges[i].end.saturating_add(1) {
                 i += 1;
                 continue;
             }
@@ -186,17 +437,13 @@ impl RangeSet {
             // must be merged:
             //
             // max] [max => right range contains end of left range => merge
-            if self.ranges[i + 1]
-                .contains_point(self.ranges[i].end.saturating_add(1))
-            {
-                self.ranges[i].end = self.ranges[i + 1].end;
+            if ranges[i + 1].contains_point(ranges[i].end.saturating_add(1)) {
+                ranges[i].end = ranges[i + 1].end;
             }
 
             // At this point the two ranges have been merged into the first
-            // one. Remove the second range from the list and decrement the
-            // counter of used array positions.
-            self.ranges.copy_within(i + 2..self.in_use, i + 1);
-            self.in_use -= 1;
+            // one. Remove the second range from the storage.
+            self.storage.remove(i + 1);
         }
     }
 
@@ -212,71 +459,65 @@ impl RangeSet {
     /// overlappings to delete, split or shrink existing ranges if necessary.
     pub fn remove(&mut self, range: Range) -> Result<(), Error> {
         let mut i = 0;
-        while i < self.in_use {
-            // Given that the internal `range` array is sorted, once the start
-            // point of a range is above the end point of the range to remove,
-            // it is not necessary to continue iterating.
-            if self.ranges[i].start > range.end {
+        while i < self.storage.len() {
+            let existing = self.storage.as_slice()[i];
+
+            // Given that the internal storage is sorted, once the start point
+            // of a range is above the end point of the range to remove, it is
+            // not necessary to continue iterating.
+            if existing.start > range.end {
                 break;
             }
 
             // If the ranges do not overlap, advance.
-            if !self.ranges[i].overlaps(range) {
+            if !existing.overlaps(range) {
                 i += 1;
                 continue;
             }
 
-            if self.ranges[i].contains_range(range) {
+            if existing.contains_range(range) {
                 // The range to be removed is contained by the existing range.
-                if self.ranges[i] == range {
+                if existing == range {
                     // The range to be removed matches the existing range.
                     // Then, the existing range must be removed.
-                    self.ranges.copy_within(i + 1..self.in_use, i);
-                    self.in_use -= 1;
-                } else if self.ranges[i].start == range.start {
+                    self.storage.remove(i);
+                } else if existing.start == range.start {
                     // The range to be removed and the existing range share the
                     // same start point. Then, it is enough with updating the
                     // start point of the existing range.
-                    self.ranges[i].start = range.end + 1;
-                } else if self.ranges[i].end == range.end {
+                    self.storage.as_mut_slice()[i].start = range.end + 1;
+                } else if existing.end == range.end {
                     // The range to be removed and the existing range share the
                     // same end point. Then, it is enough with updating the end
                     // point of the existing range.
-                    self.ranges[i].end = range.start - 1;
+                    self.storage.as_mut_slice()[i].end = range.start - 1;
                 } else {
                     // The range to be removed is in the middle of the existing
                     // range. Then, the existing range must be split and the
                     // start and end points of the new ranges updated
                     // accordingly.
-                    if self.in_use >= self.ranges.len() {
-                        return Err(Error::FullRangeSet);
-                    }
-
                     let new_range =
-                        Range::new(self.ranges[i].start, range.start - 1)?;
-                    self.ranges.copy_within(i..self.in_use, i + 1);
-                    self.ranges[i] = new_range;
-                    self.ranges[i + 1].start = range.end + 1;
-                    self.in_use += 1;
+                        Range::new(existing.start, range.start - 1)?;
+                    self.storage.insert(i, new_range)?;
+                    self.storage.as_mut_slice()[i + 1].start = range.end + 1;
                 }
 
                 break;
-            } else if range.contains_range(self.ranges[i]) {
+            } else if range.contains_range(existing) {
                 // The range to be removed contains the existing range. Then,
                 // the existing range must be removed.
-                self.ranges.copy_within(i + 1..self.in_use, i);
-                self.in_use -= 1;
-            } else if self.ranges[i].contains_point(range.start) {
+                self.storage.remove(i);
+            } else if existing.contains_point(range.start) {
                 // The start point of the range to be removed is contained by
                 // the existing range. Then, the end point of the existing
                 // range must be updated.
-                self.ranges[i].end = range.start - 1;
+                self.storage.as_mut_slice()[i].end = range.start - 1;
                 i += 1;
             } else {
                 // The end point of the range to be removed is contained by the
                 // existing range. Then, the start point of the existing range
                 // must be updated.
-                self.ranges[i].start = range.end + 1;
+                self.storage.as_mut_slice()[i].start = range.end + 1;
                 i += 1;
             }
         }
@@ -298,10 +539,203 @@ impl RangeSet {
     pub fn end(&self) -> Option<u64> {
         self.ranges().last().map(|r| r.end())
     }
+
+    /// Returns an iterator over the ranges in the `RangeSet`, sorted by their
+    /// start point.
+    pub fn iter(&self) -> slice::Iter<'_, Range> {
+        self.ranges().iter()
+    }
+
+    /// Returns the index of the range that contains `point`, if any.
+    fn find(&self, point: u64) -> Option<usize> {
+        let ranges = self.ranges();
+
+        // Index of the first range that starts after `point`. Given that the
+        // ranges are sorted and merged, only the previous one can contain it.
+        let idx = ranges.partition_point(|r| r.start <= point);
+        let idx = idx.checked_sub(1)?;
+        ranges[idx].contains_point(point).then_some(idx)
+    }
+
+    /// Returns `true` if the `RangeSet` contains a given point.
+    pub fn contains_point(&self, point: u64) -> bool {
+        self.find(point).is_some()
+    }
+
+    /// Returns `true` if the `RangeSet` contains every point of a given
+    /// range.
+    pub fn contains_range(&self, range: Range) -> bool {
+        // Contiguous ranges are merged, so the whole range must be contained
+        // by a single range of the set.
+        self.find(range.start)
+            .is_some_and(|idx| self.ranges()[idx].contains_range(range))
+    }
+
+    /// Returns `true` if any range of the `RangeSet` overlaps with a given
+    /// range.
+    pub fn overlaps(&self, range: Range) -> bool {
+        self.gaps(range).next() != Some(range)
+    }
+
+    /// Returns an iterator over the maximal ranges within `bounds` that are
+    /// not contained by the `RangeSet`, sorted by their start point.
+    pub fn gaps(&self, bounds: Range) -> Gaps<'_> {
+        Gaps {
+            ranges: self.iter(),
+            cursor: Some(bounds.start),
+            bounds_end: bounds.end,
+        }
+    }
+
+    /// Returns the first range of `size` points within `bounds` that does not
+    /// overlap with the `RangeSet`. It returns `None` if there is no gap big
+    /// enough or `size` is zero.
+    pub fn first_gap(&self, bounds: Range, size: u64) -> Option<Range> {
+        let last = size.checked_sub(1)?;
+        self.gaps(bounds)
+            .find(|gap| gap.end - gap.start >= last)
+            .map(|gap| Range {
+                start: gap.start,
+                end: gap.start + last,
+            })
+    }
+
+    /// Returns the lowest range of `size` points that is contained by the
+    /// `RangeSet` and whose start point is aligned to `align`. It returns
+    /// `None` if there is no such range or `size` is zero.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `align` is not a power of two.
+    pub fn first_fit(&self, size: u64, align: u64) -> Option<Range> {
+        assert!(align.is_power_of_two(), "align must be a power of two");
+
+        if size == 0 {
+            return None;
+        }
+        self.iter().find_map(|&range| fit(range, size, align))
+    }
+
+    /// Returns the complement of the `RangeSet` within `bounds`. That is,
+    /// the set of points in `bounds` that are not contained by the
+    /// `RangeSet`.
+    pub fn complement(&self, bounds: Range) -> Result<RangeSet<S>, Error>
+    where
+        S: Default,
+    {
+        let mut complement = RangeSet::with_storage(S::default());
+        for gap in self.gaps(bounds) {
+            complement.insert(gap)?;
+        }
+        Ok(complement)
+    }
+
+    /// Adds to the `RangeSet` all the ranges of `other`.
+    pub fn union_with<T: Storage>(
+        &mut self,
+        other: &RangeSet<T>,
+    ) -> Result<(), Error> {
+        for &range in other {
+            self.insert(range)?;
+        }
+        Ok(())
+    }
+
+    /// Removes from the `RangeSet` all the ranges of `other`.
+    pub fn difference_with<T: Storage>(
+        &mut self,
+        other: &RangeSet<T>,
+    ) -> Result<(), Error> {
+        for &range in other {
+            self.remove(range)?;
+        }
+        Ok(())
+    }
+
+    /// Removes from the `RangeSet` all the points that are not contained by
+    /// `other`.
+    pub fn intersect_with<T: Storage>(
+        &mut self,
+        other: &RangeSet<T>,
+    ) -> Result<(), Error> {
+        let (Some(start), Some(end)) = (self.start(), self.end()) else {
+            return Ok(());
+        };
+
+        for gap in other.gaps(Range { start, end }) {
+            self.remove(gap)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S: Storage> IntoIterator for &'a RangeSet<S> {
+    type Item = &'a Range;
+    type IntoIter = slice::Iter<'a, Range>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the gaps of a [`RangeSet`] within some bounds.
+///
+/// This structure is created by the [`RangeSet::gaps`] method.
+#[derive(Debug, Clone)]
+pub struct Gaps<'a> {
+    /// Remaining ranges of the set.
+    ranges: slice::Iter<'a, Range>,
+
+    /// Start point of the next gap candidate. `None` once the end of the
+    /// bounds has been reached.
+    cursor: Option<u64>,
+
+    /// End point of the bounds.
+    bounds_end: u64,
+}
+
+impl Iterator for Gaps<'_> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let cursor = self.cursor.filter(|&c| c <= self.bounds_end)?;
+
+            let range = match self.ranges.next() {
+                // The range is below the cursor. Skip it.
+                Some(range) if range.end < cursor => continue,
+
+                // The range starts within the bounds.
+                Some(range) if range.start <= self.bounds_end => range,
+
+                // There are no more ranges within the bounds, so the rest of
+                // the bounds is a gap.
+                _ => {
+                    self.cursor = None;
+                    return Some(Range {
+                        start: cursor,
+                        end: self.bounds_end,
+                    });
+                }
+            };
+
+            // The next candidate starts right after the range.
+            self.cursor = range.end.checked_add(1);
+
+            if range.start > cursor {
+                return Some(Range {
+                    start: cursor,
+                    end: range.start - 1,
+                });
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
     use super::*;
 
     #[test]
@@ -862,4 +1296,295 @@ mod tests {
         assert_eq!(rangeset.start(), None);
         assert_eq!(rangeset.end(), None);
     }
+
+    /// Points used to test the conversions exhaustively: the lowest and
+    /// highest values of a `u64`.
+    fn boundary_points() -> impl Iterator<Item = u64> + Clone {
+        (0..=16).chain(u64::MAX - 16..=u64::MAX)
+    }
+
+    #[test]
+    fn test_range_from_start_size() {
+        for start in boundary_points() {
+            for size in boundary_points() {
+                let range = Range::from_start_size(start, size);
+                match size.checked_sub(1).map(|last| start.checked_add(last)) {
+                    None => assert!(matches!(range, Err(Error::EmptyRange))),
+                    Some(None) => assert!(matches!(
+                        range,
+                        Err(Error::Overflow(s, z)) if (s, z) == (start, size)
+                    )),
+                    Some(Some(end)) => {
+                        let range = range.unwrap();
+                        assert_eq!(range, Range::new(start, end).unwrap());
+                        assert_eq!(range.size(), size);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_try_from_range() {
+        for start in boundary_points() {
+            for end in boundary_points() {
+                let range = Range::try_from(start..end);
+                if start < end {
+                    assert_eq!(
+                        range.unwrap(),
+                        Range::new(start, end - 1).unwrap()
+                    );
+                } else {
+                    assert!(matches!(range, Err(Error::EmptyRange)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_try_from_range_inclusive() {
+        for start in boundary_points() {
+            for end in boundary_points() {
+                let range = Range::try_from(start..=end);
+                if start <= end {
+                    let range = range.unwrap();
+                    assert_eq!(range, Range::new(start, end).unwrap());
+                    assert_eq!(ops::RangeInclusive::from(range), start..=end);
+                } else {
+                    let Err(Error::InvalidBoundaries(s, e)) = range else {
+                        panic!("unexpected result: {range:?}");
+                    };
+                    assert_eq!((s, e), (start, end));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_try_from_range_inclusive_exhausted() {
+        let mut range = 5u64..=5;
+        range.next();
+        assert!(matches!(Range::try_from(range), Err(Error::EmptyRange)));
+    }
+
+    #[test]
+    fn test_range_try_from_narrow_bounds() {
+        for start in (0..=16).chain(u32::MAX - 16..=u32::MAX) {
+            for end in (0..=16).chain(u32::MAX - 16..=u32::MAX) {
+                let expected = Range::try_from(start as u64..end as u64).ok();
+                let range = Range::try_from(start..end);
+                assert_eq!(range.ok(), expected);
+                let range = Range::try_from(start as usize..end as usize);
+                assert_eq!(range.ok(), expected);
+
+                let expected = Range::try_from(start as u64..=end as u64).ok();
+                let range = Range::try_from(start..=end);
+                assert_eq!(range.ok(), expected);
+                let range = Range::try_from(start as usize..=end as usize);
+                assert_eq!(range.ok(), expected);
+            }
+        }
+    }
+
+    /// Returns a [`RangeSet`] with the provided ranges.
+    fn rangeset_from(ranges: &[(u64, u64)]) -> RangeSet {
+        let mut rangeset = RangeSet::new();
+        for &(start, end) in ranges {
+            rangeset.insert(Range::new(start, end).unwrap()).unwrap();
+        }
+        rangeset
+    }
+
+    /// Returns a vector with the start and end points of the provided ranges.
+    fn points<'a>(
+        ranges: impl IntoIterator<Item = &'a Range>,
+    ) -> std::vec::Vec<(u64, u64)> {
+        ranges.into_iter().map(|r| (r.start(), r.end())).collect()
+    }
+
+    #[test]
+    fn test_rangeset_iter() {
+        let rangeset = rangeset_from(&[(25, 30), (5, 10)]);
+        assert_eq!(points(rangeset.iter()), [(5, 10), (25, 30)]);
+        assert_eq!(points(&rangeset), [(5, 10), (25, 30)]);
+    }
+
+    #[test]
+    fn test_rangeset_contains_point() {
+        let rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        assert!(!rangeset.contains_point(4));
+        assert!(rangeset.contains_point(5));
+        assert!(rangeset.contains_point(10));
+        assert!(!rangeset.contains_point(11));
+        assert!(!rangeset.contains_point(24));
+        assert!(rangeset.contains_point(30));
+        assert!(!rangeset.contains_point(u64::MAX));
+        assert!(!RangeSet::new().contains_point(0));
+    }
+
+    #[test]
+    fn test_rangeset_contains_range() {
+        let rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        assert!(rangeset.contains_range(Range::new(5, 10).unwrap()));
+        assert!(rangeset.contains_range(Range::new(26, 29).unwrap()));
+        assert!(!rangeset.contains_range(Range::new(4, 10).unwrap()));
+        assert!(!rangeset.contains_range(Range::new(5, 25).unwrap()));
+        assert!(!rangeset.contains_range(Range::new(11, 24).unwrap()));
+    }
+
+    #[test]
+    fn test_rangeset_overlaps() {
+        let rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        assert!(rangeset.overlaps(Range::new(0, 5).unwrap()));
+        assert!(rangeset.overlaps(Range::new(11, 25).unwrap()));
+        assert!(rangeset.overlaps(Range::new(0, u64::MAX).unwrap()));
+        assert!(!rangeset.overlaps(Range::new(11, 24).unwrap()));
+        assert!(!rangeset.overlaps(Range::new(31, u64::MAX).unwrap()));
+    }
+
+    #[test]
+    fn test_rangeset_gaps() {
+        let rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        let gaps = rangeset
+            .gaps(Range::new(0, 40).unwrap())
+            .map(|r| (r.start(), r.end()))
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(gaps, [(0, 4), (11, 24), (31, 40)]);
+
+        let gaps = rangeset
+            .gaps(Range::new(7, 27).unwrap())
+            .map(|r| (r.start(), r.end()))
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(gaps, [(11, 24)]);
+
+        assert_eq!(rangeset.gaps(Range::new(5, 10).unwrap()).next(), None);
+    }
+
+    #[test]
+    fn test_rangeset_gaps_limits() {
+        let rangeset = rangeset_from(&[(0, 10), (20, u64::MAX)]);
+        let gaps = rangeset
+            .gaps(Range::new(0, u64::MAX).unwrap())
+            .map(|r| (r.start(), r.end()))
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(gaps, [(11, 19)]);
+
+        let gaps = RangeSet::new()
+            .gaps(Range::new(0, u64::MAX).unwrap())
+            .map(|r| (r.start(), r.end()))
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(gaps, [(0, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_rangeset_first_gap() {
+        let rangeset = rangeset_from(&[(5, 10), (15, 30)]);
+        let bounds = Range::new(0, 40).unwrap();
+        assert_eq!(
+            rangeset.first_gap(bounds, 3),
+            Some(Range::new(0, 2).unwrap())
+        );
+        assert_eq!(
+            rangeset.first_gap(bounds, 5),
+            Some(Range::new(0, 4).unwrap())
+        );
+        assert_eq!(
+            rangeset.first_gap(bounds, 6),
+            Some(Range::new(31, 36).unwrap())
+        );
+        assert_eq!(
+            rangeset.first_gap(Range::new(5, 40).unwrap(), 4),
+            Some(Range::new(11, 14).unwrap())
+        );
+        assert_eq!(rangeset.first_gap(bounds, 11), None);
+        assert_eq!(rangeset.first_gap(bounds, 0), None);
+    }
+
+    #[test]
+    fn test_rangeset_complement() {
+        let rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        let complement =
+            rangeset.complement(Range::new(0, 40).unwrap()).unwrap();
+        assert_eq!(points(&complement), [(0, 4), (11, 24), (31, 40)]);
+    }
+
+    #[test]
+    fn test_rangeset_union_with() {
+        let mut rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        let other = rangeset_from(&[(0, 2), (11, 20), (40, 50)]);
+        rangeset.union_with(&other).unwrap();
+        assert_eq!(points(&rangeset), [(0, 2), (5, 20), (25, 30), (40, 50)]);
+    }
+
+    #[test]
+    fn test_rangeset_difference_with() {
+        let mut rangeset = rangeset_from(&[(5, 10), (25, 30)]);
+        let other = rangeset_from(&[(0, 5), (8, 26), (30, 30)]);
+        rangeset.difference_with(&other).unwrap();
+        assert_eq!(points(&rangeset), [(6, 7), (27, 29)]);
+    }
+
+    #[test]
+    fn test_rangeset_intersect_with() {
+        let mut rangeset = rangeset_from(&[(5, 10), (25, 30), (40, 50)]);
+        let other = rangeset_from(&[(0, 6), (8, 26), (60, 70)]);
+        rangeset.intersect_with(&other).unwrap();
+        assert_eq!(points(&rangeset), [(5, 6), (8, 10), (25, 26)]);
+
+        let mut rangeset = rangeset_from(&[(5, 10)]);
+        rangeset.intersect_with(&RangeSet::new()).unwrap();
+        assert_eq!(rangeset.ranges(), []);
+    }
+
+    #[test]
+    fn test_fixed_rangeset_full() {
+        let mut rangeset = FixedRangeSet::<2>::default();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        rangeset.insert(Range::new(20, 30).unwrap()).unwrap();
+        assert!(matches!(
+            rangeset.insert(Range::new(40, 50).unwrap()),
+            Err(Error::FullRangeSet)
+        ));
+
+        // Extending a range with the same start point does not need extra
+        // space.
+        rangeset.insert(Range::new(0, 25).unwrap()).unwrap();
+        assert_eq!(rangeset.ranges(), [Range::new(0, 30).unwrap()]);
+    }
+
+    #[test]
+    fn test_fixed_rangeset_remove_split_full() {
+        let mut rangeset = FixedRangeSet::<1>::default();
+        rangeset.insert(Range::new(0, 10).unwrap()).unwrap();
+        assert!(matches!(
+            rangeset.remove(Range::new(5, 5).unwrap()),
+            Err(Error::FullRangeSet)
+        ));
+        assert_eq!(rangeset.ranges(), [Range::new(0, 10).unwrap()]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_vec_rangeset_grows() {
+        let mut rangeset = VecRangeSet::default();
+        rangeset
+            .insert(Range::new(0, 4 * RANGE_SET_SIZE as u64).unwrap())
+            .unwrap();
+
+        // Punch more holes than fit in the default fixed-size storage.
+        for i in 0..2 * RANGE_SET_SIZE as u64 {
+            rangeset
+                .remove(Range::new(2 * i + 1, 2 * i + 1).unwrap())
+                .unwrap();
+        }
+        assert_eq!(rangeset.ranges().len(), 2 * RANGE_SET_SIZE + 1);
+
+        rangeset
+            .insert(Range::new(0, 4 * RANGE_SET_SIZE as u64).unwrap())
+            .unwrap();
+        assert_eq!(
+            rangeset.ranges(),
+            [Range::new(0, 4 * RANGE_SET_SIZE as u64).unwrap()]
+        );
+    }
 }
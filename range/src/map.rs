@@ -0,0 +1,449 @@
+//! Map of disjoint ranges to values.
+
+use core::fmt;
+use core::slice;
+
+use crate::{Error, Range};
+
+/// Size of the fixed-size array that contains the entries of the default
+/// `RangeMap`.
+pub const RANGE_MAP_SIZE: usize = 64;
+
+/// Associates values with non-overlapping ranges.
+///
+/// Inserting a range overwrites the values of the points it covers, so the
+/// existing ranges are shrunk or split if necessary. Contiguous ranges with
+/// equal values are merged.
+///
+/// The entries are kept in a fixed-size array that can hold up to `N`
+/// ranges. Thus, it does not require an allocator.
+#[derive(Clone)]
+pub struct RangeMap<V, const N: usize = RANGE_MAP_SIZE> {
+    /// Ranges of the entries, sorted by their start point.
+    ranges: [Range; N],
+
+    /// Values of the entries. The value at a given index is associated with
+    /// the range at the same index of `ranges`. Only the first `in_use`
+    /// elements are `Some`.
+    values: [Option<V>; N],
+
+    /// Number of entries that are being used.
+    in_use: usize,
+}
+
+impl<V, const N: usize> RangeMap<V, N> {
+    /// Returns an empty `RangeMap`.
+    pub const fn new() -> Self {
+        RangeMap {
+            ranges: [Range { start: 0, end: 0 }; N],
+            values: [const { None }; N],
+            in_use: 0,
+        }
+    }
+
+    /// Returns the number of ranges in the `RangeMap`.
+    pub fn len(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns `true` if the `RangeMap` does not contain any range.
+    pub fn is_empty(&self) -> bool {
+        self.in_use == 0
+    }
+
+    /// Returns the ranges in the `RangeMap`.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges[..self.in_use]
+    }
+
+    /// Returns an iterator over the ranges in the `RangeMap` and their
+    /// values, sorted by the start point of the ranges.
+    pub fn iter(&self) -> RangeMapIter<'_, V> {
+        RangeMapIter {
+            ranges: self.ranges().iter(),
+            values: self.values[..self.in_use].iter(),
+        }
+    }
+
+    /// Returns the range that contains `point` and its value, if any.
+    pub fn get_entry(&self, point: u64) -> Option<(Range, &V)> {
+        // Index of the first range that starts after `point`. Given that the
+        // ranges do not overlap, only the previous one can contain it.
+        let idx = self.ranges().partition_point(|r| r.start <= point);
+        let idx = idx.checked_sub(1)?;
+        let range = self.ranges[idx];
+        range
+            .contains_point(point)
+            .then(|| (range, self.value(idx)))
+    }
+
+    /// Returns the value associated with `point`, if any.
+    pub fn get(&self, point: u64) -> Option<&V> {
+        self.get_entry(point).map(|(_, value)| value)
+    }
+
+    /// Removes all the ranges from the `RangeMap`.
+    pub fn clear(&mut self) {
+        self.values[..self.in_use].fill_with(|| None);
+        self.in_use = 0;
+    }
+
+    /// Returns the value at position `idx`, which must be in use.
+    fn value(&self, idx: usize) -> &V {
+        self.values[idx].as_ref().expect("RangeMap entry is in use")
+    }
+
+    /// Inserts an entry at position `idx`, shifting all the entries after it
+    /// forward. The caller must ensure that there is space for it.
+    fn insert_at(&mut self, idx: usize, range: Range, value: V) {
+        self.ranges.copy_within(idx..self.in_use, idx + 1);
+        self.ranges[idx] = range;
+        self.values[idx..=self.in_use].rotate_right(1);
+        self.values[idx] = Some(value);
+        self.in_use += 1;
+    }
+
+    /// Removes the entry at position `idx`, shifting all the entries after it
+    /// backward.
+    fn remove_at(&mut self, idx: usize) {
+        self.ranges.copy_within(idx + 1..self.in_use, idx);
+        self.values[idx..self.in_use].rotate_left(1);
+        self.in_use -= 1;
+        self.values[self.in_use] = None;
+    }
+
+    /// Checks that there is enough space to remove `range` from the map and,
+    /// then, add `added` entries.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeMap` if there is not enough
+    /// space.
+    fn check_capacity(&self, range: Range, added: usize) -> Result<(), Error> {
+        let mut len = self.in_use + added;
+        for existing in self.ranges().iter().filter(|r| r.overlaps(range)) {
+            if existing.start < range.start && existing.end > range.end {
+                // The existing range must be split.
+                len += 1;
+            } else if range.contains_range(*existing) {
+                // The existing range must be removed.
+                len -= 1;
+            }
+        }
+
+        if len > N {
+            Err(Error::FullRangeMap)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<V: Clone, const N: usize> RangeMap<V, N> {
+    /// Removes `range` from the existing entries, shrinking, splitting or
+    /// removing them if necessary. It returns the position where an entry
+    /// starting at `range.start` must be inserted. The caller must ensure
+    /// that there is space for the entries resulting from a split.
+    fn carve(&mut self, range: Range) -> usize {
+        // Index of the first range that ends within or after `range`.
+        let mut idx = self.ranges().partition_point(|r| r.end < range.start);
+
+        while idx < self.in_use && self.ranges[idx].start <= range.end {
+            let existing = self.ranges[idx];
+
+            if existing.start < range.start && existing.end > range.end {
+                // The range is in the middle of the existing range. Then, the
+                // existing range must be split.
+                let value = self.value(idx).clone();
+                let tail = Range {
+                    start: range.end + 1,
+                    end: existing.end,
+                };
+                self.ranges[idx].end = range.start - 1;
+                self.insert_at(idx + 1, tail, value);
+                return idx + 1;
+            } else if existing.start < range.start {
+                // The start point of the range is contained by the existing
+                // range. Then, its end point must be updated.
+                self.ranges[idx].end = range.start - 1;
+                idx += 1;
+            } else if existing.end > range.end {
+                // The end point of the range is contained by the existing
+                // range. Then, its start point must be updated. Given that the
+                // ranges are sorted, no more ranges can overlap.
+                self.ranges[idx].start = range.end + 1;
+                break;
+            } else {
+                // The range contains the existing range. Then, the existing
+                // range must be removed.
+                self.remove_at(idx);
+            }
+        }
+
+        idx
+    }
+
+    /// Removes a `Range` from the `RangeMap`. The existing ranges are
+    /// shrunk, split or removed if necessary.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeMap` if an existing range must
+    /// be split and there is no space for the new range. In that case, the
+    /// `RangeMap` is not modified.
+    pub fn remove(&mut self, range: Range) -> Result<(), Error> {
+        self.check_capacity(range, 0)?;
+        self.carve(range);
+        Ok(())
+    }
+}
+
+impl<V: Clone + PartialEq, const N: usize> RangeMap<V, N> {
+    /// Associates `value` with every point of `range`. The existing ranges
+    /// that overlap with it are shrunk, split or removed, and contiguous
+    /// ranges with equal values are merged.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeMap` if there is no space for
+    /// the new ranges. In that case, the `RangeMap` is not modified.
+    pub fn insert(&mut self, range: Range, value: V) -> Result<(), Error> {
+        // If the range is already associated with the same value, there is
+        // nothing to do. This avoids splitting the existing range only to
+        // merge it again afterwards.
+        if self
+            .get_entry(range.start)
+            .is_some_and(|(r, v)| r.contains_range(range) && *v == value)
+        {
+            return Ok(());
+        }
+
+        self.check_capacity(range, 1)?;
+        let mut idx = self.carve(range);
+        self.insert_at(idx, range, value);
+
+        // Merge with the previous range if they are contiguous and their
+        // values are equal.
+        if idx > 0
+            && self.ranges[idx - 1].end + 1 == range.start
+            && self.value(idx - 1) == self.value(idx)
+        {
+            self.ranges[idx - 1].end = range.end;
+            self.remove_at(idx);
+            idx -= 1;
+        }
+
+        // Merge with the next range if they are contiguous and their values
+        // are equal.
+        if idx + 1 < self.in_use
+            && self.ranges[idx].end.checked_add(1)
+                == Some(self.ranges[idx + 1].start)
+            && self.value(idx) == self.value(idx + 1)
+        {
+            self.ranges[idx].end = self.ranges[idx + 1].end;
+            self.remove_at(idx + 1);
+        }
+
+        Ok(())
+    }
+}
+
+impl<V, const N: usize> Default for RangeMap<V, N> {
+    fn default() -> Self {
+        RangeMap::new()
+    }
+}
+
+impl<V: fmt::Debug, const N: usize> fmt::Debug for RangeMap<V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, V, const N: usize> IntoIterator for &'a RangeMap<V, N> {
+    type Item = (Range, &'a V);
+    type IntoIter = RangeMapIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of a [`RangeMap`].
+///
+/// This structure is created by the [`RangeMap::iter`] method.
+#[derive(Debug, Clone)]
+pub struct RangeMapIter<'a, V> {
+    /// Remaining ranges.
+    ranges: slice::Iter<'a, Range>,
+
+    /// Remaining values.
+    values: slice::Iter<'a, Option<V>>,
+}
+
+impl<'a, V> Iterator for RangeMapIter<'a, V> {
+    type Item = (Range, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let range = *self.ranges.next()?;
+        let value = self.values.next()?.as_ref()?;
+        Some((range, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::vec::Vec;
+
+    /// Returns a vector with the start and end points of the ranges in `map`
+    /// and their values.
+    fn entries<V: Copy, const N: usize>(
+        map: &RangeMap<V, N>,
+    ) -> Vec<(u64, u64, V)> {
+        map.iter().map(|(r, &v)| (r.start(), r.end(), v)).collect()
+    }
+
+    #[test]
+    fn test_rangemap_insert() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(25, 30).unwrap(), 'b').unwrap();
+        map.insert(Range::new(5, 10).unwrap(), 'a').unwrap();
+        assert_eq!(entries(&map), [(5, 10, 'a'), (25, 30, 'b')]);
+    }
+
+    #[test]
+    fn test_rangemap_insert_overwrite() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(0, 10).unwrap(), 'a').unwrap();
+        map.insert(Range::new(15, 30).unwrap(), 'b').unwrap();
+        map.insert(Range::new(5, 20).unwrap(), 'c').unwrap();
+        assert_eq!(entries(&map), [(0, 4, 'a'), (5, 20, 'c'), (21, 30, 'b')]);
+
+        map.insert(Range::new(0, 30).unwrap(), 'd').unwrap();
+        assert_eq!(entries(&map), [(0, 30, 'd')]);
+    }
+
+    #[test]
+    fn test_rangemap_insert_split() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(0, 30).unwrap(), 'a').unwrap();
+        map.insert(Range::new(10, 20).unwrap(), 'b').unwrap();
+        assert_eq!(entries(&map), [(0, 9, 'a'), (10, 20, 'b'), (21, 30, 'a')]);
+    }
+
+    #[test]
+    fn test_rangemap_insert_merge() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(0, 9).unwrap(), 'a').unwrap();
+        map.insert(Range::new(20, 30).unwrap(), 'a').unwrap();
+        map.insert(Range::new(10, 19).unwrap(), 'b').unwrap();
+        assert_eq!(map.len(), 3);
+
+        map.insert(Range::new(10, 19).unwrap(), 'a').unwrap();
+        assert_eq!(entries(&map), [(0, 30, 'a')]);
+    }
+
+    #[test]
+    fn test_rangemap_insert_same_value() {
+        let mut map = RangeMap::<char, 1>::new();
+        map.insert(Range::new(0, 30).unwrap(), 'a').unwrap();
+        map.insert(Range::new(10, 20).unwrap(), 'a').unwrap();
+        assert_eq!(entries(&map), [(0, 30, 'a')]);
+    }
+
+    #[test]
+    fn test_rangemap_insert_limits() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(0, u64::MAX).unwrap(), 'a').unwrap();
+        map.insert(Range::new(u64::MAX, u64::MAX).unwrap(), 'b')
+            .unwrap();
+        map.insert(Range::new(0, 0).unwrap(), 'b').unwrap();
+        assert_eq!(
+            entries(&map),
+            [
+                (0, 0, 'b'),
+                (1, u64::MAX - 1, 'a'),
+                (u64::MAX, u64::MAX, 'b')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rangemap_remove() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(0, 10).unwrap(), 'a').unwrap();
+        map.insert(Range::new(11, 20).unwrap(), 'b').unwrap();
+        map.insert(Range::new(21, 30).unwrap(), 'c').unwrap();
+
+        map.remove(Range::new(5, 25).unwrap()).unwrap();
+        assert_eq!(entries(&map), [(0, 4, 'a'), (26, 30, 'c')]);
+
+        map.remove(Range::new(2, 2).unwrap()).unwrap();
+        assert_eq!(entries(&map), [(0, 1, 'a'), (3, 4, 'a'), (26, 30, 'c')]);
+
+        map.remove(Range::new(0, u64::MAX).unwrap()).unwrap();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_rangemap_get() {
+        let mut map = RangeMap::<char>::new();
+        map.insert(Range::new(5, 10).unwrap(), 'a').unwrap();
+        map.insert(Range::new(25, 30).unwrap(), 'b').unwrap();
+
+        assert_eq!(map.get(4), None);
+        assert_eq!(map.get(5), Some(&'a'));
+        assert_eq!(map.get(10), Some(&'a'));
+        assert_eq!(map.get(11), None);
+        assert_eq!(
+            map.get_entry(27),
+            Some((Range::new(25, 30).unwrap(), &'b'))
+        );
+        assert_eq!(map.get(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_rangemap_full() {
+        let mut map = RangeMap::<char, 2>::new();
+        map.insert(Range::new(0, 30).unwrap(), 'a').unwrap();
+
+        // Splitting the existing range needs two extra entries.
+        assert!(matches!(
+            map.insert(Range::new(10, 20).unwrap(), 'b'),
+            Err(Error::FullRangeMap)
+        ));
+        assert_eq!(entries(&map), [(0, 30, 'a')]);
+
+        map.remove(Range::new(10, 20).unwrap()).unwrap();
+        assert!(matches!(
+            map.remove(Range::new(2, 2).unwrap()),
+            Err(Error::FullRangeMap)
+        ));
+        assert_eq!(entries(&map), [(0, 9, 'a'), (21, 30, 'a')]);
+
+        // Overwriting an entry does not need extra space.
+        map.insert(Range::new(0, 9).unwrap(), 'b').unwrap();
+        assert_eq!(entries(&map), [(0, 9, 'b'), (21, 30, 'a')]);
+    }
+
+    #[test]
+    fn test_rangemap_drop_values() {
+        let value = std::sync::Arc::new(());
+
+        let mut map = RangeMap::<_, 4>::new();
+        map.insert(Range::new(0, 30).unwrap(), value.clone())
+            .unwrap();
+        map.remove(Range::new(10, 20).unwrap()).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&value), 3);
+
+        map.remove(Range::new(0, 9).unwrap()).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&value), 2);
+
+        map.clear();
+        assert_eq!(std::sync::Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,812 @@
+//! Set of ranges backed by a balanced tree.
+
+use core::cmp::{max, min};
+use core::fmt;
+
+use crate::{fit, Error, Range, RANGE_SET_SIZE};
+
+/// Index used to represent the absence of a node.
+const NIL: usize = usize::MAX;
+
+/// Node of a [`TreeRangeSet`].
+#[derive(Debug, Copy, Clone)]
+struct Node {
+    /// Range stored in the node.
+    range: Range,
+
+    /// Index of the left child. Its subtree contains the ranges below
+    /// `range`.
+    left: usize,
+
+    /// Index of the right child. Its subtree contains the ranges above
+    /// `range`. For unused nodes, it is the index of the next unused node.
+    right: usize,
+
+    /// Height of the subtree rooted at this node.
+    height: u8,
+
+    /// Greatest `end - start` of the ranges in the subtree rooted at this
+    /// node. It is used to skip the subtrees that cannot satisfy a
+    /// [`TreeRangeSet::first_fit`] request. The size is not stored, because
+    /// it overflows for the range `[0, u64::MAX]`.
+    max_len: u64,
+
+    /// Greatest [`max_order`] of the ranges in the subtree rooted at this
+    /// node. Like `max_len`, it is used to skip subtrees, but it takes the
+    /// alignment of the request into account.
+    max_order: u8,
+}
+
+impl Node {
+    /// Unused node.
+    const UNUSED: Node = Node {
+        range: Range { start: 0, end: 0 },
+        left: NIL,
+        right: NIL,
+        height: 0,
+        max_len: 0,
+        max_order: 0,
+    };
+}
+
+/// Returns the greatest `k` such that `range` contains a block of `2^k`
+/// points whose start point is aligned to `2^k`.
+fn max_order(range: Range) -> u8 {
+    // Any `2^k` consecutive points contain an aligned block of `2^(k-1)`
+    // points, so the order is either `k` or `k - 1`.
+    let k = match (range.end - range.start).checked_add(1) {
+        Some(size) => size.ilog2(),
+        None => u64::BITS - 1,
+    };
+    if fit(range, 1 << k, 1 << k).is_some() {
+        k as u8
+    } else {
+        (k - 1) as u8
+    }
+}
+
+/// Represents a set of ranges backed by a balanced tree.
+///
+/// It offers the same semantics as a [`RangeSet`](crate::RangeSet), but
+/// insertions, removals and lookups take logarithmic time. So, it is better
+/// suited for sets with many ranges, like the free memory of a fragmented
+/// heap.
+///
+/// The ranges are kept in an AVL tree sorted by their start point. Every node
+/// also tracks the longest range in its subtree and the biggest aligned
+/// block of a power of two size that fits in it. This allows finding the
+/// first range that can hold an allocation without visiting the ranges that
+/// are too small or too misaligned. See [`TreeRangeSet::first_fit`].
+///
+/// The nodes of the tree are stored in a fixed-size array that can hold up to
+/// `N` ranges. Thus, it does not require an allocator.
+#[derive(Copy, Clone)]
+pub struct TreeRangeSet<const N: usize = RANGE_SET_SIZE> {
+    /// Nodes of the tree.
+    nodes: [Node; N],
+
+    /// Index of the root node.
+    root: usize,
+
+    /// Index of the first unused node. The unused nodes are linked through
+    /// their `right` field.
+    free: usize,
+
+    /// Number of nodes that are being used.
+    in_use: usize,
+}
+
+impl<const N: usize> TreeRangeSet<N> {
+    /// Returns an empty `TreeRangeSet`.
+    pub const fn new() -> Self {
+        let mut nodes = [Node::UNUSED; N];
+
+        let mut i = 0;
+        while i + 1 < N {
+            nodes[i].right = i + 1;
+            i += 1;
+        }
+
+        TreeRangeSet {
+            nodes,
+            root: NIL,
+            free: if N > 0 { 0 } else { NIL },
+            in_use: 0,
+        }
+    }
+
+    /// Returns the number of ranges in the `TreeRangeSet`.
+    pub fn len(&self) -> usize {
+        self.in_use
+    }
+
+    /// Returns `true` if the `TreeRangeSet` does not contain any range.
+    pub fn is_empty(&self) -> bool {
+        self.in_use == 0
+    }
+
+    /// Inserts a `Range` into the `TreeRangeSet`. It takes into account
+    /// possible overlappings to create, merge or enlarge existing ranges if
+    /// necessary.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeSet` if there is no space for
+    /// the new range. In that case, the `TreeRangeSet` is not modified.
+    pub fn insert(&mut self, range: Range) -> Result<(), Error> {
+        let mut range = range;
+
+        // Merge with the range that starts at or before the new one if they
+        // overlap or are contiguous. We use `saturating_add` because in the
+        // following case the ranges must be merged:
+        //
+        // max] [max => max <= max == true => merge
+        if let Some(prev) = self.floor(range.start) {
+            if range.start <= prev.end.saturating_add(1) {
+                range.start = prev.start;
+                range.end = max(range.end, prev.end);
+                self.root = self.delete(self.root, prev.start);
+            }
+        }
+
+        // Merge with the ranges that start within or right after the new one.
+        while let Some(next) = self.ceil(range.start) {
+            if next.start > range.end.saturating_add(1) {
+                break;
+            }
+            range.end = max(range.end, next.end);
+            self.root = self.delete(self.root, next.start);
+        }
+
+        // If any range has been merged, there is at least one unused node.
+        // Otherwise, the set has not been modified.
+        self.insert_node(range)
+    }
+
+    /// Removes a `Range` from the `TreeRangeSet`. It takes into account
+    /// possible overlappings to delete, split or shrink existing ranges if
+    /// necessary.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `Error::FullRangeSet` if an existing range must
+    /// be split and there is no space for the new range. In that case, the
+    /// `TreeRangeSet` is not modified.
+    pub fn remove(&mut self, range: Range) -> Result<(), Error> {
+        // Handle the range that starts at or before the range to remove.
+        if let Some(prev) = self.floor(range.start) {
+            if prev.end >= range.start {
+                let split = prev.start < range.start && prev.end > range.end;
+                if split && self.in_use >= N {
+                    return Err(Error::FullRangeSet);
+                }
+
+                self.root = self.delete(self.root, prev.start);
+
+                // The following insertions cannot fail. The first one reuses
+                // the deleted node and there is space for the second one.
+                if prev.start < range.start {
+                    self.insert_node(Range {
+                        start: prev.start,
+                        end: range.start - 1,
+                    })?;
+                }
+                if prev.end > range.end {
+                    self.insert_node(Range {
+                        start: range.end + 1,
+                        end: prev.end,
+                    })?;
+                    return Ok(());
+                }
+            }
+        }
+
+        // Handle the ranges that start within the range to remove.
+        while let Some(next) = self.ceil(range.start) {
+            if next.start > range.end {
+                break;
+            }
+
+            self.root = self.delete(self.root, next.start);
+            if next.end > range.end {
+                // This reuses the deleted node.
+                self.insert_node(Range {
+                    start: range.end + 1,
+                    end: next.end,
+                })?;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the sum of the size of all the ranges in the `TreeRangeSet`.
+    pub fn size(&self) -> u64 {
+        self.iter().map(|r| r.size()).sum()
+    }
+
+    /// Returns the lowest start point of the `TreeRangeSet`.
+    pub fn start(&self) -> Option<u64> {
+        self.ceil(0).map(|r| r.start)
+    }
+
+    /// Returns the highest end point of the `TreeRangeSet`.
+    pub fn end(&self) -> Option<u64> {
+        self.floor(u64::MAX).map(|r| r.end)
+    }
+
+    /// Returns an iterator over the ranges in the `TreeRangeSet`, sorted by
+    /// their start point.
+    pub fn iter(&self) -> TreeRangeSetIter<'_, N> {
+        TreeRangeSetIter {
+            set: self,
+            cursor: Some(0),
+        }
+    }
+
+    /// Returns `true` if the `TreeRangeSet` contains a given point.
+    pub fn contains_point(&self, point: u64) -> bool {
+        self.floor(point).is_some_and(|r| r.contains_point(point))
+    }
+
+    /// Returns `true` if the `TreeRangeSet` contains every point of a given
+    /// range.
+    pub fn contains_range(&self, range: Range) -> bool {
+        self.floor(range.start)
+            .is_some_and(|r| r.contains_range(range))
+    }
+
+    /// Returns the lowest range of `size` points that is contained by the
+    /// `TreeRangeSet` and whose start point is aligned to `align`. It returns
+    /// `None` if there is no such range or `size` is zero.
+    ///
+    /// It takes logarithmic time when `size` is a power of two not greater
+    /// than `align`, like the allocations of pages. In that case, a subtree
+    /// is skipped if and only if none of its ranges can hold the request.
+    /// Otherwise, a subtree may hold ranges long enough and aligned blocks
+    /// big enough that still do not fit the request, so the search may visit
+    /// up to all the ranges in the worst case.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `align` is not a power of two.
+    pub fn first_fit(&self, size: u64, align: u64) -> Option<Range> {
+        assert!(align.is_power_of_two(), "align must be a power of two");
+
+        let last = size.checked_sub(1)?;
+
+        // A fit starts at a point aligned to `align` and contains at least
+        // `2^size.ilog2()` points, so it contains an aligned block of the
+        // smallest of both powers of two.
+        let order = min(size.ilog2(), align.trailing_zeros()) as u8;
+
+        self.first_fit_in(self.root, last, order, size, align)
+    }
+
+    /// Returns the lowest fit in the subtree rooted at `n`. See
+    /// [`TreeRangeSet::first_fit`].
+    fn first_fit_in(
+        &self,
+        n: usize,
+        last: u64,
+        order: u8,
+        size: u64,
+        align: u64,
+    ) -> Option<Range> {
+        // Skip the subtrees without any range big enough or without any
+        // aligned block big enough.
+        if n == NIL
+            || self.nodes[n].max_len < last
+            || self.nodes[n].max_order < order
+        {
+            return None;
+        }
+
+        let node = &self.nodes[n];
+        self.first_fit_in(node.left, last, order, size, align)
+            .or_else(|| fit(node.range, size, align))
+            .or_else(|| self.first_fit_in(node.right, last, order, size, align))
+    }
+
+    /// Returns the range with the greatest start point that is lower than or
+    /// equal to `point`.
+    fn floor(&self, point: u64) -> Option<Range> {
+        let mut found = None;
+        let mut n = self.root;
+        while n != NIL {
+            let node = &self.nodes[n];
+            if node.range.start <= point {
+                found = Some(node.range);
+                n = node.right;
+            } else {
+                n = node.left;
+            }
+        }
+        found
+    }
+
+    /// Returns the range with the lowest start point that is greater than or
+    /// equal to `point`.
+    fn ceil(&self, point: u64) -> Option<Range> {
+        let mut found = None;
+        let mut n = self.root;
+        while n != NIL {
+            let node = &self.nodes[n];
+            if node.range.start >= point {
+                found = Some(node.range);
+                n = node.left;
+            } else {
+                n = node.right;
+            }
+        }
+        found
+    }
+
+    /// Inserts a node with `range` into the tree. The range must not overlap
+    /// with the ranges in the tree.
+    fn insert_node(&mut self, range: Range) -> Result<(), Error> {
+        if self.free == NIL {
+            return Err(Error::FullRangeSet);
+        }
+
+        let n = self.free;
+        self.free = self.nodes[n].right;
+        self.in_use += 1;
+
+        self.nodes[n] = Node {
+            range,
+            left: NIL,
+            right: NIL,
+            height: 1,
+            max_len: range.end - range.start,
+            max_order: max_order(range),
+        };
+        self.root = self.attach(self.root, n);
+
+        Ok(())
+    }
+
+    /// Attaches the node `new` to the subtree rooted at `n`. It returns the
+    /// new root of the subtree.
+    fn attach(&mut self, n: usize, new: usize) -> usize {
+        if n == NIL {
+            return new;
+        }
+
+        if self.nodes[new].range.start < self.nodes[n].range.start {
+            self.nodes[n].left = self.attach(self.nodes[n].left, new);
+        } else {
+            self.nodes[n].right = self.attach(self.nodes[n].right, new);
+        }
+        self.rebalance(n)
+    }
+
+    /// Deletes the node whose range starts at `start` from the subtree rooted
+    /// at `n` and marks it as unused. It returns the new root of the subtree.
+    fn delete(&mut self, n: usize, start: u64) -> usize {
+        if n == NIL {
+            return NIL;
+        }
+
+        let node = self.nodes[n];
+        if start < node.range.start {
+            self.nodes[n].left = self.delete(node.left, start);
+            return self.rebalance(n);
+        }
+        if start > node.range.start {
+            self.nodes[n].right = self.delete(node.right, start);
+            return self.rebalance(n);
+        }
+
+        self.nodes[n] = Node::UNUSED;
+        self.nodes[n].right = self.free;
+        self.free = n;
+        self.in_use -= 1;
+
+        if node.right == NIL {
+            return node.left;
+        }
+
+        // Replace the node with its successor.
+        let (right, succ) = self.detach_min(node.right);
+        self.nodes[succ].left = node.left;
+        self.nodes[succ].right = right;
+        self.rebalance(succ)
+    }
+
+    /// Detaches the node with the lowest range from the subtree rooted at
+    /// `n`. It returns the new root of the subtree and the detached node.
+    fn detach_min(&mut self, n: usize) -> (usize, usize) {
+        let node = self.nodes[n];
+        if node.left == NIL {
+            return (node.right, n);
+        }
+
+        let (left, min) = self.detach_min(node.left);
+        self.nodes[n].left = left;
+        (self.rebalance(n), min)
+    }
+
+    /// Returns the height of the subtree rooted at `n`.
+    fn height(&self, n: usize) -> u8 {
+        if n == NIL {
+            0
+        } else {
+            self.nodes[n].height
+        }
+    }
+
+    /// Updates the height and the longest range of the node `n` from its
+    /// children.
+    fn update(&mut self, n: usize) {
+        let node = self.nodes[n];
+
+        let mut max_len = node.range.end - node.range.start;
+        let mut order = max_order(node.range);
+        for child in [node.left, node.right] {
+            if child != NIL {
+                max_len = max(max_len, self.nodes[child].max_len);
+                order = max(order, self.nodes[child].max_order);
+            }
+        }
+
+        self.nodes[n].height =
+            1 + max(self.height(node.left), self.height(node.right));
+        self.nodes[n].max_len = max_len;
+        self.nodes[n].max_order = order;
+    }
+
+    /// Rotates the subtree rooted at `n` to the right. It returns the new
+    /// root of the subtree.
+    fn rotate_right(&mut self, n: usize) -> usize {
+        let left = self.nodes[n].left;
+        self.nodes[n].left = self.nodes[left].right;
+        self.nodes[left].right = n;
+        self.update(n);
+        self.update(left);
+        left
+    }
+
+    /// Rotates the subtree rooted at `n` to the left. It returns the new root
+    /// of the subtree.
+    fn rotate_left(&mut self, n: usize) -> usize {
+        let right = self.nodes[n].right;
+        self.nodes[n].right = self.nodes[right].left;
+        self.nodes[right].left = n;
+        self.update(n);
+        self.update(right);
+        right
+    }
+
+    /// Restores the balance of the subtree rooted at `n`, assuming that the
+    /// heights of its children differ at most by two. It returns the new root
+    /// of the subtree.
+    fn rebalance(&mut self, n: usize) -> usize {
+        self.update(n);
+
+        let Node { left, right, .. } = self.nodes[n];
+        let (left_height, right_height) =
+            (self.height(left), self.height(right));
+
+        if left_height > right_height + 1 {
+            let Node {
+                left: ll,
+                right: lr,
+                ..
+            } = self.nodes[left];
+            if self.height(ll) < self.height(lr) {
+                self.nodes[n].left = self.rotate_left(left);
+            }
+            self.rotate_right(n)
+        } else if right_height > left_height + 1 {
+            let Node {
+                left: rl,
+                right: rr,
+                ..
+            } = self.nodes[right];
+            if self.height(rr) < self.height(rl) {
+                self.nodes[n].right = self.rotate_right(right);
+            }
+            self.rotate_left(n)
+        } else {
+            n
+        }
+    }
+}
+
+impl<const N: usize> Default for TreeRangeSet<N> {
+    fn default() -> Self {
+        TreeRangeSet::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for TreeRangeSet<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a TreeRangeSet<N> {
+    type Item = Range;
+    type IntoIter = TreeRangeSetIter<'a, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the ranges of a [`TreeRangeSet`].
+///
+/// This structure is created by the [`TreeRangeSet::iter`] method.
+#[derive(Debug, Clone)]
+pub struct TreeRangeSetIter<'a, const N: usize> {
+    /// The set being iterated.
+    set: &'a TreeRangeSet<N>,
+
+    /// Lowest start point of the next range. `None` once the range ending at
+    /// `u64::MAX` has been returned.
+    cursor: Option<u64>,
+}
+
+impl<const N: usize> Iterator for TreeRangeSetIter<'_, N> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let range = self.set.ceil(self.cursor?)?;
+        self.cursor = range.end.checked_add(1);
+        Some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::FixedRangeSet;
+
+    use std::vec::Vec;
+
+    impl<const N: usize> TreeRangeSet<N> {
+        /// Checks the invariants of the subtree rooted at `n` and returns its
+        /// height.
+        fn check_subtree(&self, n: usize, lo: u64, hi: u64) -> u8 {
+            if n == NIL {
+                return 0;
+            }
+
+            let node = self.nodes[n];
+            assert!(node.range.start >= lo && node.range.end <= hi);
+
+            let left_height = match node.range.start.checked_sub(1) {
+                Some(hi) => self.check_subtree(node.left, lo, hi),
+                None => self.check_subtree(node.left, 1, 0),
+            };
+            let right_height = match node.range.end.checked_add(1) {
+                Some(lo) => self.check_subtree(node.right, lo, hi),
+                None => self.check_subtree(node.right, 1, 0),
+            };
+            assert!(left_height.abs_diff(right_height) <= 1);
+            assert_eq!(node.height, 1 + max(left_height, right_height));
+
+            let mut max_len = node.range.end - node.range.start;
+            let mut order = max_order(node.range);
+            for child in [node.left, node.right] {
+                if child != NIL {
+                    max_len = max(max_len, self.nodes[child].max_len);
+                    order = max(order, self.nodes[child].max_order);
+                }
+            }
+            assert_eq!(node.max_len, max_len);
+            assert_eq!(node.max_order, order);
+
+            node.height
+        }
+
+        /// Checks the invariants of the tree.
+        fn check(&self) {
+            self.check_subtree(self.root, 0, u64::MAX);
+
+            // Contiguous ranges must have been merged.
+            let ranges = self.iter().collect::<Vec<_>>();
+            assert_eq!(ranges.len(), self.len());
+            for pair in ranges.windows(2) {
+                assert!(pair[0].end + 1 < pair[1].start);
+            }
+        }
+    }
+
+    /// Returns a vector with the start and end points of the ranges in `set`.
+    fn points<const N: usize>(set: &TreeRangeSet<N>) -> Vec<(u64, u64)> {
+        set.iter().map(|r| (r.start(), r.end())).collect()
+    }
+
+    #[test]
+    fn test_treerangeset_insert() {
+        let mut set = TreeRangeSet::<16>::new();
+        set.insert(Range::new(25, 30).unwrap()).unwrap();
+        set.insert(Range::new(5, 10).unwrap()).unwrap();
+        set.insert(Range::new(15, 20).unwrap()).unwrap();
+        set.check();
+        assert_eq!(points(&set), [(5, 10), (15, 20), (25, 30)]);
+
+        set.insert(Range::new(11, 14).unwrap()).unwrap();
+        set.insert(Range::new(19, 27).unwrap()).unwrap();
+        set.check();
+        assert_eq!(points(&set), [(5, 30)]);
+    }
+
+    #[test]
+    fn test_treerangeset_insert_limits() {
+        let mut set = TreeRangeSet::<16>::new();
+        set.insert(Range::new(u64::MAX, u64::MAX).unwrap()).unwrap();
+        set.insert(Range::new(0, 0).unwrap()).unwrap();
+        assert_eq!(points(&set), [(0, 0), (u64::MAX, u64::MAX)]);
+
+        set.insert(Range::new(1, u64::MAX - 1).unwrap()).unwrap();
+        set.check();
+        assert_eq!(points(&set), [(0, u64::MAX)]);
+        assert_eq!(
+            set.first_fit(u64::MAX, 1),
+            Range::new(0, u64::MAX - 1).ok()
+        );
+    }
+
+    #[test]
+    fn test_treerangeset_remove() {
+        let mut set = TreeRangeSet::<16>::new();
+        set.insert(Range::new(0, 10).unwrap()).unwrap();
+        set.insert(Range::new(20, 30).unwrap()).unwrap();
+        set.insert(Range::new(40, 50).unwrap()).unwrap();
+
+        set.remove(Range::new(5, 45).unwrap()).unwrap();
+        set.check();
+        assert_eq!(points(&set), [(0, 4), (46, 50)]);
+
+        set.remove(Range::new(2, 2).unwrap()).unwrap();
+        set.check();
+        assert_eq!(points(&set), [(0, 1), (3, 4), (46, 50)]);
+
+        set.remove(Range::new(0, u64::MAX).unwrap()).unwrap();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_treerangeset_full() {
+        let mut set = TreeRangeSet::<2>::new();
+        set.insert(Range::new(0, 10).unwrap()).unwrap();
+        set.insert(Range::new(20, 30).unwrap()).unwrap();
+        assert!(matches!(
+            set.insert(Range::new(40, 50).unwrap()),
+            Err(Error::FullRangeSet)
+        ));
+        assert!(matches!(
+            set.remove(Range::new(5, 5).unwrap()),
+            Err(Error::FullRangeSet)
+        ));
+        assert_eq!(points(&set), [(0, 10), (20, 30)]);
+
+        // Merging ranges frees space.
+        set.insert(Range::new(11, 19).unwrap()).unwrap();
+        set.insert(Range::new(40, 50).unwrap()).unwrap();
+        assert_eq!(points(&set), [(0, 30), (40, 50)]);
+    }
+
+    #[test]
+    fn test_treerangeset_first_fit() {
+        let mut set = TreeRangeSet::<16>::new();
+        set.insert(Range::new(0x1001, 0x1fff).unwrap()).unwrap();
+        set.insert(Range::new(0x3000, 0x3fff).unwrap()).unwrap();
+        set.insert(Range::new(0x8000, 0xffff).unwrap()).unwrap();
+
+        assert_eq!(set.first_fit(0x10, 1), Range::new(0x1001, 0x1010).ok());
+        assert_eq!(set.first_fit(0x10, 0x10), Range::new(0x1010, 0x101f).ok());
+        assert_eq!(set.first_fit(0x1000, 1), Range::new(0x3000, 0x3fff).ok());
+        assert_eq!(
+            set.first_fit(0x1000, 0x4000),
+            Range::new(0x8000, 0x8fff).ok()
+        );
+        assert_eq!(set.first_fit(0x8001, 1), None);
+        assert_eq!(set.first_fit(0, 1), None);
+    }
+
+    #[test]
+    fn test_treerangeset_max_order() {
+        let order = |start, end| max_order(Range::new(start, end).unwrap());
+        assert_eq!(order(0, 0), 0);
+        assert_eq!(order(1, 0x1000), 11);
+        assert_eq!(order(0x1000, 0x1fff), 12);
+        assert_eq!(order(0x1001, 0x3000), 12);
+        assert_eq!(order(0, u64::MAX), 63);
+        assert_eq!(order(1, u64::MAX), 63);
+        assert_eq!(order(1, u64::MAX - 1), 62);
+    }
+
+    #[test]
+    fn test_treerangeset_first_fit_aligned() {
+        const ALIGN: u64 = 0x1000;
+
+        // Ranges of `ALIGN` points starting at odd offsets are long enough,
+        // but they cannot hold an aligned block of `ALIGN` points.
+        let mut set = TreeRangeSet::<1024>::new();
+        for i in 0..1000 {
+            let start = 2 * i * ALIGN + 1;
+            set.insert(Range::from_start_size(start, ALIGN).unwrap())
+                .unwrap();
+        }
+        set.check();
+        assert_eq!(set.first_fit(ALIGN, ALIGN), None);
+        assert_eq!(
+            set.first_fit(ALIGN / 2, ALIGN / 2),
+            Range::from_start_size(ALIGN / 2, ALIGN / 2).ok()
+        );
+
+        set.insert(Range::from_start_size(1 << 32, ALIGN).unwrap())
+            .unwrap();
+        set.check();
+        assert_eq!(
+            set.first_fit(ALIGN, ALIGN),
+            Range::from_start_size(1 << 32, ALIGN).ok()
+        );
+        assert_eq!(
+            set.first_fit(ALIGN - 1, ALIGN),
+            Range::from_start_size(1 << 32, ALIGN - 1).ok()
+        );
+    }
+
+    #[test]
+    fn test_treerangeset_contains() {
+        let mut set = TreeRangeSet::<16>::new();
+        set.insert(Range::new(5, 10).unwrap()).unwrap();
+        set.insert(Range::new(25, 30).unwrap()).unwrap();
+
+        assert!(!set.contains_point(4));
+        assert!(set.contains_point(5));
+        assert!(set.contains_point(30));
+        assert!(!set.contains_point(31));
+        assert!(set.contains_range(Range::new(26, 29).unwrap()));
+        assert!(!set.contains_range(Range::new(5, 25).unwrap()));
+        assert_eq!(set.start(), Some(5));
+        assert_eq!(set.end(), Some(30));
+        assert_eq!(set.size(), 12);
+    }
+
+    #[test]
+    fn test_treerangeset_random() {
+        let mut set = TreeRangeSet::<1024>::new();
+        let mut reference = FixedRangeSet::<1024>::default();
+
+        // xorshift64.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut rand = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let start = rand() % 1024;
+            let end = start + rand() % 64;
+            let range = Range::new(start, end).unwrap();
+
+            let result = if rand() & 1 == 0 {
+                (set.insert(range), reference.insert(range))
+            } else {
+                (set.remove(range), reference.remove(range))
+            };
+            assert_eq!(result.0.is_ok(), result.1.is_ok());
+
+            set.check();
+            assert_eq!(set.iter().collect::<Vec<_>>(), reference.ranges());
+
+            let size = rand() % 64 + 1;
+            let align = 1 << (rand() % 6);
+            assert_eq!(
+                set.first_fit(size, align),
+                reference.first_fit(size, align)
+            );
+        }
+    }
+}